@@ -1,24 +1,52 @@
-use clap::{Args, Parser, Subcommand};
+use chrono::{DateTime, Utc};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use regex::Regex;
+use serde::Serialize;
+use std::fmt;
+
+use crate::error::EXIT_CODES;
+use crate::logs::parse_time;
+
+/// How long start waits for roxy to respond on /alive
+pub const START_TIMEOUT_SECS: u64 = 30;
 
 #[derive(Debug, Parser)]
-#[clap(author = "keifufu", version)]
+#[clap(author = "keifufu", version, after_help = EXIT_CODES)]
 pub struct RoxyCliArgs {
   #[clap(subcommand)]
   pub command: RoxySubcommand,
+
+  /// How to print results
+  #[clap(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+  pub format: OutputFormat,
+
+  /// Print the result as one JSON object, short for --format json
+  #[clap(long, global = true)]
+  pub json: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+  Text,
+  Json,
 }
 
 #[derive(Debug, Subcommand)]
 pub enum RoxySubcommand {
   Status,
-  Install(InstallCommand),
+  Install(Box<InstallCommand>),
   Uninstall,
-  Update,
-  Start,
-  Restart,
+  Setup(SetupCommand),
+  Update(UpdateCommand),
+  Start(StartCommand),
+  Restart(StartCommand),
   Stop,
+  Run,
+  Supervise(SuperviseCommand),
+  Watchdog(WatchdogOptions),
   Autostart(AutostartCommand),
-  Config,
-  Logs,
+  Config(ConfigCommand),
+  Logs(LogsCommand),
 }
 
 #[derive(Debug, Args)]
@@ -28,12 +56,251 @@ pub struct InstallCommand {
   /// - Windows: %LocalAppData%\roxy
   /// - Linux: /home/<user>/.roxy
   pub path: Option<String>,
+
+  /// Roxy release to install, e.g. 1.0.1 (defaults to the main branch)
+  #[clap(long, conflicts_with_all = ["git_ref", "from"])]
+  pub version: Option<String>,
+
+  /// Git branch, tag or commit to install
+  #[clap(long = "ref", conflicts_with = "from")]
+  pub git_ref: Option<String>,
+
+  /// Roxy archive to install from, either a local path or a URL
+  #[clap(long)]
+  pub from: Option<String>,
+
+  /// Node.js version to install
+  #[clap(long, default_value = "18.16.0")]
+  pub node_version: String,
+
+  /// Node.js archive to install from, either a local path or a URL
+  #[clap(long, conflicts_with = "node_version")]
+  pub node_from: Option<String>,
+
+  #[clap(flatten)]
+  pub verify: VerifyArgs,
+
+  /// Expected SHA-256 of the Node.js archive.
+  /// Official Node.js downloads are checked against SHASUMS256.txt
+  #[clap(long)]
+  pub node_sha256: Option<String>,
+
+  /// Show npm output while building
+  #[clap(short, long)]
+  pub verbose: bool,
+
+  /// Do not verify downloaded archives
+  #[clap(long, conflicts_with_all = ["sha256", "checksums", "public_key", "node_sha256"])]
+  pub skip_verify: bool,
+
+  #[clap(flatten)]
+  pub setup: SetupCommand,
+}
+
+#[derive(Debug, Args)]
+pub struct SetupCommand {
+  /// Do not ask, take the settings from the flags and keep the rest as they are
+  #[clap(long)]
+  pub non_interactive: bool,
+
+  #[clap(flatten)]
+  pub settings: SetupSettings,
+}
+
+// Settings of roxy.json that setup asks for, flattened into install and setup
+#[derive(Debug, Args)]
+pub struct SetupSettings {
+  /// Public URL roxy is reached at, e.g. https://roxy.example.com
+  #[clap(long)]
+  pub url: Option<String>,
+
+  /// Port roxy listens on
+  #[clap(long)]
+  pub port: Option<u16>,
+
+  /// Serve HTTPS directly instead of leaving it to a reverse proxy
+  #[clap(long)]
+  pub use_https: Option<bool>,
+
+  /// SSL certificate to serve HTTPS with
+  #[clap(long)]
+  pub ssl_cert_path: Option<String>,
+
+  /// SSL private key to serve HTTPS with
+  #[clap(long)]
+  pub ssl_key_path: Option<String>,
+
+  /// Whether roxy is behind a reverse proxy, so it trusts X-Forwarded-* headers
+  #[clap(long)]
+  pub is_proxied: Option<bool>,
+
+  /// Whether anyone can register an account
+  #[clap(long)]
+  pub allow_registrations: Option<bool>,
+}
+
+#[derive(Debug, Args)]
+pub struct UpdateCommand {
+  /// Show npm output while building
+  #[clap(short, long)]
+  pub verbose: bool,
+
+  #[clap(flatten)]
+  pub verify: VerifyArgs,
+
+  /// Do not verify the downloaded archive
+  #[clap(long, conflicts_with_all = ["sha256", "checksums", "public_key"])]
+  pub skip_verify: bool,
+}
+
+// How the roxy archive is verified, flattened into install and update
+#[derive(Debug, Args)]
+pub struct VerifyArgs {
+  /// Expected SHA-256 of the roxy archive
+  #[clap(long, conflicts_with = "checksums")]
+  pub sha256: Option<String>,
+
+  /// SHASUMS256.txt manifest for the roxy archive, either a local path or a URL.
+  /// Defaults to the release manifest when installing with --version, and when updating
+  #[clap(long)]
+  pub checksums: Option<String>,
+
+  /// Minisign public key the roxy archive has to be signed with
+  #[clap(long)]
+  pub public_key: Option<String>,
+
+  /// Minisign signature of the roxy archive, either a local path or a URL.
+  /// Defaults to the archive source with a .minisig suffix
+  #[clap(long, requires = "public_key")]
+  pub signature: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct StartCommand {
+  /// Seconds to wait for roxy to respond before giving up
+  #[clap(long, default_value_t = START_TIMEOUT_SECS)]
+  pub timeout: u64,
+}
+
+#[derive(Debug, Args)]
+pub struct SuperviseCommand {
+  /// Crashes in a row after which roxy is no longer restarted
+  #[clap(long, default_value_t = 5)]
+  pub max_restarts: u32,
+
+  /// Also restart roxy when it stops responding on /alive
+  #[clap(long)]
+  pub watchdog: bool,
+
+  #[clap(flatten)]
+  pub watchdog_options: WatchdogOptions,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct WatchdogOptions {
+  /// Seconds between /alive probes
+  #[clap(long, default_value_t = 10)]
+  pub interval: u64,
+
+  /// Seconds a probe may take before it counts as failed
+  #[clap(long, default_value_t = 5)]
+  pub probe_timeout: u64,
+
+  /// Milliseconds after which a slow response counts as a failed probe
+  #[clap(long, default_value_t = 2000)]
+  pub max_latency: u64,
+
+  /// Failed probes in a row after which roxy is considered hung
+  #[clap(long, default_value_t = 3)]
+  pub failures: u32,
+
+  /// Seconds to give a freshly started roxy before probing it
+  #[clap(long, default_value_t = START_TIMEOUT_SECS)]
+  pub startup_grace: u64,
+}
+
+#[derive(Debug, Args)]
+pub struct LogsCommand {
+  /// Number of entries to show, defaults to all (10 when following)
+  #[clap(short = 'n', long)]
+  pub lines: Option<usize>,
+
+  /// Keep printing new entries as they are logged
+  #[clap(short, long)]
+  pub follow: bool,
+
+  /// Only show entries of this level or above
+  #[clap(long, value_enum)]
+  pub level: Option<LogLevel>,
+
+  /// Only show entries logged after this, e.g. 30m, 2h, 7d, 2023-07-01 or 2023-07-01 12:00:00
+  #[clap(long, value_parser = parse_time)]
+  pub since: Option<DateTime<Utc>>,
+
+  /// Only show entries logged before this, in the same formats as --since
+  #[clap(long, value_parser = parse_time)]
+  pub until: Option<DateTime<Utc>>,
+
+  /// Only show entries matching this regular expression
+  #[clap(long)]
+  pub grep: Option<Regex>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+  Log,
+  Warn,
+  Err,
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigCommand {
+  #[clap(subcommand)]
+  pub command: ConfigSubcommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigSubcommand {
+  /// Print the value of a roxy.json key
+  Get { key: String },
+  /// Change a roxy.json key, e.g. `config set port 8080`
+  Set { key: String, value: String },
+  /// Reset a roxy.json key to the server's default
+  Unset { key: String },
+  /// Print every roxy.json key and its value
+  List,
+  /// Open roxy.json in an editor
+  Edit,
 }
 
 #[derive(Debug, Args)]
 pub struct AutostartCommand {
   #[clap(subcommand)]
   pub command: AutostartSubcommand,
+
+  /// Service manager to use instead of detecting one
+  #[clap(long, global = true, value_enum)]
+  pub service_manager: Option<ServiceManagerKind>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ServiceManagerKind {
+  SystemdUser,
+  SystemdSystem,
+  Openrc,
+  Runit,
+  Xdg,
+  Registry,
+}
+
+impl fmt::Display for ServiceManagerKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self.to_possible_value() {
+      Some(value) => write!(f, "{}", value.get_name()),
+      None => write!(f, "{:?}", self),
+    }
+  }
 }
 
 #[derive(Debug, Subcommand)]
@@ -0,0 +1,260 @@
+use indicatif::ProgressBar;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+
+use crate::error::RoxyCliError;
+use crate::utils::{log_tail, InstallInfo};
+
+/// Lines of the install log shown when a build step fails
+const LOG_TAIL_LINES: usize = 20;
+
+/// Installs dependencies and builds the roxy tree at `roxy_path`.
+///
+/// Output of every npm step is written to the install log in the data path,
+/// and also printed live if `verbose` is set.
+pub fn build_roxy(
+  install_info: &InstallInfo,
+  roxy_path: &Path,
+  verbose: bool,
+  pb: &ProgressBar,
+) -> Result<(), RoxyCliError> {
+  let log_path = install_info.install_log_path();
+  let mut log = File::create(&log_path)
+    .map_err(RoxyCliError::io(format!("Failed to create {:?}", log_path)))?;
+
+  let package = PackageJson::read(roxy_path)?;
+  let mut steps = vec![vec!["install"]];
+  if package.scripts.contains_key("build") {
+    steps.push(vec!["run", "build"]);
+  }
+
+  for args in steps {
+    let mut command = npm_command(install_info);
+    command.args(&args).current_dir(roxy_path);
+
+    let name = format!("npm {}", args.join(" "));
+    if let Err(err) = run_logged(&name, command, &mut log, verbose, pb) {
+      return Err(RoxyCliError::ChildProcess {
+        message: format!("{}\n\n{}", err, log_tail(&log_path, LOG_TAIL_LINES)),
+        log: (!verbose).then_some(log_path),
+      });
+    }
+  }
+
+  Ok(())
+}
+
+/// The parts of roxy's package.json the CLI cares about.
+#[derive(Deserialize)]
+pub struct PackageJson {
+  pub version: String,
+  pub main: Option<String>,
+  #[serde(default)]
+  pub scripts: HashMap<String, String>,
+}
+
+impl PackageJson {
+  pub fn read(roxy_path: &Path) -> Result<Self, RoxyCliError> {
+    let path = roxy_path.join("package.json");
+    let contents =
+      fs::read_to_string(&path).map_err(RoxyCliError::io(format!("Failed to read {:?}", path)))?;
+    serde_json::from_str(&contents).map_err(|err| RoxyCliError::ConfigParse {
+      path,
+      reason: err.to_string(),
+    })
+  }
+}
+
+/// How the server is launched.
+pub enum Entrypoint {
+  /// `main` from package.json, run directly with node
+  Main(PathBuf),
+  /// The `start` script, run through npm
+  StartScript(String),
+}
+
+impl Entrypoint {
+  /// Picks the built `main` if it exists, then falls back to the `start` script.
+  /// Makes sure whatever it picks can actually be run, a build that did not
+  /// produce it fails like the build itself.
+  pub fn resolve(roxy_path: &Path) -> Result<Self, RoxyCliError> {
+    let not_runnable = |message: String| RoxyCliError::ChildProcess { message, log: None };
+    let package = PackageJson::read(roxy_path)?;
+
+    if let Some(main) = &package.main {
+      if roxy_path.join(main).is_file() {
+        return Ok(Self::Main(roxy_path.join(main)));
+      }
+    }
+
+    let script = match package.scripts.get("start") {
+      Some(script) => script,
+      None => {
+        return Err(not_runnable(match &package.main {
+          Some(main) => format!(
+            "{} does not exist and package.json has no start script",
+            main
+          ),
+          None => String::from("package.json has neither main nor a start script"),
+        }))
+      }
+    };
+
+    // e.g. "ts-node --transpile-only ./src/index.ts"
+    let mut words = script.split_whitespace();
+    let program = words
+      .next()
+      .ok_or_else(|| not_runnable(String::from("The start script is empty")))?;
+    if program != "node"
+      && !roxy_path
+        .join("node_modules")
+        .join(".bin")
+        .join(program)
+        .exists()
+    {
+      return Err(not_runnable(format!(
+        "The start script runs {}, which is not installed",
+        program
+      )));
+    }
+    for file in words.filter(|word| is_script_file(word)) {
+      if !roxy_path.join(file).is_file() {
+        return Err(not_runnable(format!(
+          "The start script runs {}, which does not exist",
+          file
+        )));
+      }
+    }
+
+    Ok(Self::StartScript(script.clone()))
+  }
+
+  pub fn command(&self, install_info: &InstallInfo, roxy_path: &Path) -> Command {
+    match self {
+      Self::Main(main) => {
+        #[cfg(target_os = "windows")]
+        let mut command = Command::new(install_info.node_path().join("node.exe"));
+        #[cfg(target_os = "linux")]
+        let mut command = Command::new(install_info.node_path().join("bin").join("node"));
+
+        command.arg(main);
+        command
+      }
+      Self::StartScript(_) => {
+        let mut command = npm_command(install_info);
+        command.arg("start").current_dir(roxy_path);
+        command
+      }
+    }
+  }
+}
+
+impl fmt::Display for Entrypoint {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Main(main) => write!(f, "node {}", main.to_string_lossy()),
+      Self::StartScript(script) => write!(f, "npm start ({})", script),
+    }
+  }
+}
+
+fn is_script_file(word: &str) -> bool {
+  [".js", ".cjs", ".mjs", ".ts"]
+    .iter()
+    .any(|extension| word.ends_with(extension))
+}
+
+/// npm, with our Node.js first in PATH so scripts run with it too
+fn npm_command(install_info: &InstallInfo) -> Command {
+  #[cfg(target_os = "windows")]
+  let (bin_path, mut command) = (
+    install_info.node_path(),
+    Command::new(install_info.node_path().join("npm.cmd")),
+  );
+
+  #[cfg(target_os = "linux")]
+  let (bin_path, mut command) = {
+    let bin_path = install_info.node_path().join("bin");
+    let mut command = Command::new(bin_path.join("node"));
+    command.arg(bin_path.join("npm"));
+    (bin_path, command)
+  };
+
+  let mut paths = vec![bin_path];
+  if let Some(path) = std::env::var_os("PATH") {
+    paths.extend(std::env::split_paths(&path));
+  }
+  command.env(
+    "PATH",
+    std::env::join_paths(paths).unwrap_or_else(|_| OsString::new()),
+  );
+  command
+}
+
+fn run_logged(
+  name: &str,
+  mut command: Command,
+  log: &mut File,
+  verbose: bool,
+  pb: &ProgressBar,
+) -> Result<(), String> {
+  let log_error = |err: std::io::Error| format!("Failed to write install log: {}", err);
+  writeln!(log, "==> {}", name).map_err(log_error)?;
+
+  let mut child = command
+    .stdin(Stdio::null())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .map_err(|err| format!("Failed to run `{}`: {}", name, err))?;
+
+  // stdout and stderr are read on their own threads so neither pipe fills up and blocks npm
+  let (tx, rx) = mpsc::channel();
+  let readers = [
+    forward_lines(child.stdout.take(), tx.clone()),
+    forward_lines(child.stderr.take(), tx),
+  ];
+  for line in rx {
+    writeln!(log, "{}", line).map_err(log_error)?;
+    if verbose {
+      pb.println(&line);
+    }
+  }
+  for reader in readers {
+    reader.join().ok();
+  }
+
+  let status = child
+    .wait()
+    .map_err(|err| format!("Failed to wait for `{}`: {}", name, err))?;
+  writeln!(log, "==> {} exited with {}\n", name, status).map_err(log_error)?;
+
+  if status.success() {
+    Ok(())
+  } else {
+    Err(format!("`{}` exited with {}", name, status))
+  }
+}
+
+fn forward_lines<R: Read + Send + 'static>(
+  pipe: Option<R>,
+  tx: mpsc::Sender<String>,
+) -> thread::JoinHandle<()> {
+  thread::spawn(move || {
+    if let Some(pipe) = pipe {
+      for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+        if tx.send(line).is_err() {
+          break;
+        }
+      }
+    }
+  })
+}
@@ -0,0 +1,407 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use crate::error::RoxyCliError;
+use crate::jsonc;
+use crate::utils::{write_private, InstallInfo};
+
+/// roxy.json, mirroring `ConfigType` in roxy/src/utils/config.ts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoxyConfig {
+  #[serde(rename = "_", default = "default_comment")]
+  pub comment: String,
+  pub url: String,
+  pub port: u16,
+  pub use_https: bool,
+  pub ssl_cert_path: Option<String>,
+  pub ssl_key_path: Option<String>,
+  pub is_proxied: bool,
+  pub allow_registrations: bool,
+  pub default_limits_total_mb: u64,
+  pub default_limits_custom_urls: u64,
+  pub url_shortener_key_length: u32,
+  pub paste_key_length: u32,
+  pub file_key_length: u32,
+  pub global_rate_limit_per_second: u32,
+}
+
+fn default_comment() -> String {
+  String::from("Read more on the Wiki: https://github.com/keifufu/roxy/wiki")
+}
+
+/// What the server writes on its first start
+impl Default for RoxyConfig {
+  fn default() -> Self {
+    Self {
+      comment: default_comment(),
+      url: String::from("https://dev.keifufu.dev"),
+      port: 7227,
+      use_https: false,
+      ssl_cert_path: None,
+      ssl_key_path: None,
+      is_proxied: true,
+      allow_registrations: true,
+      default_limits_total_mb: 50,
+      default_limits_custom_urls: 0,
+      url_shortener_key_length: 5,
+      paste_key_length: 5,
+      file_key_length: 5,
+      global_rate_limit_per_second: 1000,
+    }
+  }
+}
+
+impl RoxyConfig {
+  pub fn path(install_info: &InstallInfo) -> PathBuf {
+    install_info.path.join("roxy.json")
+  }
+
+  /// Reads roxy.json, or the server's defaults if it has not written one yet
+  pub fn read(install_info: &InstallInfo) -> Result<Self, RoxyCliError> {
+    let path = Self::path(install_info);
+    let contents = match fs::read_to_string(&path) {
+      Ok(contents) => contents,
+      Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+      Err(err) => return Err(RoxyCliError::io(format!("Failed to read {:?}", path))(err)),
+    };
+    Self::parse(&contents).map_err(|reason| parse_error(path, reason))
+  }
+
+  /// Parses roxy.json, naming the field that is missing or has the wrong type.
+  /// Comments and trailing commas are allowed.
+  pub fn parse(contents: &str) -> Result<Self, String> {
+    let contents = jsonc::strip(contents);
+    let deserializer = &mut serde_json::Deserializer::from_str(&contents);
+    serde_path_to_error::deserialize(deserializer).map_err(describe_error)
+  }
+
+  /// Like `parse`, for values that are no longer text, so errors have no
+  /// line and column
+  fn from_values(values: Map<String, Value>) -> Result<Self, String> {
+    serde_path_to_error::deserialize(Value::Object(values)).map_err(describe_error)
+  }
+
+  /// Problems the server would only run into at runtime, if at all
+  pub fn validate(&self) -> Result<(), String> {
+    let problems = self.problems();
+    if problems.is_empty() {
+      Ok(())
+    } else {
+      Err(problems.join("\n"))
+    }
+  }
+
+  fn problems(&self) -> Vec<String> {
+    let mut problems = Vec::new();
+    if self.port == 0 {
+      problems.push(String::from("`port` has to be between 1 and 65535"));
+    }
+    for (key, length) in [
+      ("urlShortenerKeyLength", self.url_shortener_key_length),
+      ("pasteKeyLength", self.paste_key_length),
+      ("fileKeyLength", self.file_key_length),
+    ] {
+      if length < 1 {
+        problems.push(format!("`{}` has to be at least 1", key));
+      }
+    }
+    if self.use_https {
+      for (key, path) in [
+        ("sslCertPath", &self.ssl_cert_path),
+        ("sslKeyPath", &self.ssl_key_path),
+      ] {
+        match path {
+          None => problems.push(format!("`{}` has to be set when `useHttps` is true", key)),
+          Some(path) if !Path::new(path).exists() => {
+            problems.push(format!("`{}` {:?} does not exist", key, path))
+          }
+          Some(_) => {}
+        }
+      }
+    }
+    problems
+  }
+
+  /// URL of `path` on the local server
+  pub fn local_url(&self, path: &str) -> String {
+    format!(
+      "{}://localhost:{}{}",
+      if self.use_https { "https" } else { "http" },
+      self.port,
+      path
+    )
+  }
+}
+
+/// roxy.json as a list of keys in file order, for changing single keys.
+/// Changes are made to the text itself, so comments are kept.
+pub struct RoxyConfigFile {
+  pub path: PathBuf,
+  /// As read, or the defaults as the server would write them
+  contents: String,
+  values: Map<String, Value>,
+}
+
+impl RoxyConfigFile {
+  /// Reads roxy.json, or the server's defaults if it has not written one yet
+  pub fn read(install_info: &InstallInfo) -> Result<Self, RoxyCliError> {
+    let path = RoxyConfig::path(install_info);
+    let contents = match fs::read_to_string(&path) {
+      Ok(contents) => contents,
+      Err(err) if err.kind() == ErrorKind::NotFound => {
+        let values = default_values();
+        // Like the server's JSON.stringify(config, null, 2)
+        let contents = serde_json::to_string_pretty(&values)
+          .map_err(|err| RoxyCliError::Other(format!("Failed to serialize roxy.json: {}", err)))?;
+        return Ok(Self {
+          path,
+          contents,
+          values,
+        });
+      }
+      Err(err) => return Err(RoxyCliError::io(format!("Failed to read {:?}", path))(err)),
+    };
+
+    Self::parse(path.clone(), contents).map_err(|reason| parse_error(path, reason))
+  }
+
+  /// Parses the contents of roxy.json, checking only that it is an object
+  pub fn parse(path: PathBuf, contents: String) -> Result<Self, String> {
+    let values = match serde_json::from_str(&jsonc::strip(&contents)) {
+      Ok(Value::Object(values)) => values,
+      Ok(_) => return Err(String::from("expected an object")),
+      Err(err) => return Err(err.to_string()),
+    };
+    Ok(Self {
+      path,
+      contents,
+      values,
+    })
+  }
+
+  pub fn contents(&self) -> &str {
+    &self.contents
+  }
+
+  /// Every key of roxy.json, in the order the server writes them
+  pub fn keys() -> Vec<String> {
+    default_values().keys().cloned().collect()
+  }
+
+  pub fn values(&self) -> &Map<String, Value> {
+    &self.values
+  }
+
+  pub fn get(&self, key: &str) -> Result<&Value, RoxyCliError> {
+    check_key(key)?;
+    self
+      .values
+      .get(key)
+      .ok_or_else(|| parse_error(self.path.clone(), format!("missing field `{}`", key)))
+  }
+
+  /// Sets `key` from a command line value, typed like the key's default.
+  /// Returns whether the value changed.
+  pub fn set(&mut self, key: &str, value: &str) -> Result<bool, RoxyCliError> {
+    check_key(key)?;
+    let value = parse_value(key, value).map_err(RoxyCliError::InvalidArgument)?;
+    self.replace(key, value)
+  }
+
+  /// Resets `key` to the server's default. Returns whether the value changed.
+  pub fn unset(&mut self, key: &str) -> Result<bool, RoxyCliError> {
+    check_key(key)?;
+    let value = default_values()[key].clone();
+    self.replace(key, value)
+  }
+
+  /// Sets several keys at once, only checking the result as a whole.
+  /// Returns the keys whose value changed.
+  pub fn set_values(&mut self, values: Vec<(&str, Value)>) -> Result<Vec<String>, RoxyCliError> {
+    let before = self.values.clone();
+    let mut keys = Vec::new();
+    let mut changed = Vec::new();
+    for (key, value) in values {
+      check_key(key)?;
+      keys.push(key);
+      if self.splice(key, value)? {
+        changed.push(key.to_string());
+      }
+    }
+    self
+      .check_change(&before, &keys)
+      .map_err(RoxyCliError::InvalidArgument)?;
+    Ok(changed)
+  }
+
+  fn replace(&mut self, key: &str, value: Value) -> Result<bool, RoxyCliError> {
+    self
+      .set_values(vec![(key, value)])
+      .map(|changed| !changed.is_empty())
+  }
+
+  /// Checks only what setting `keys` changes. Keys that were already missing,
+  /// of the wrong type or invalid are left to be fixed on their own, or a
+  /// broken file could never be fixed one key at a time.
+  fn check_change(&self, before: &Map<String, Value>, keys: &[&str]) -> Result<(), String> {
+    let usable = usable_values(before);
+    let known_problems = RoxyConfig::from_values(usable.clone())
+      .map(|config| config.problems())
+      .unwrap_or_default();
+
+    let mut values = usable;
+    for key in keys {
+      values.insert(key.to_string(), self.values[*key].clone());
+    }
+    let problems: Vec<String> = RoxyConfig::from_values(values)?
+      .problems()
+      .into_iter()
+      .filter(|problem| !known_problems.contains(problem))
+      .collect();
+    if problems.is_empty() {
+      Ok(())
+    } else {
+      Err(problems.join("\n"))
+    }
+  }
+
+  fn splice(&mut self, key: &str, value: Value) -> Result<bool, RoxyCliError> {
+    if self.values.get(key) == Some(&value) {
+      return Ok(false);
+    }
+    self.contents = jsonc::set(&self.contents, key, &value)
+      .map_err(|reason| parse_error(self.path.clone(), reason))?;
+    self.values.insert(key.to_string(), value);
+    Ok(true)
+  }
+
+  /// Keys whose value differs in `other`
+  pub fn changed_keys(&self, other: &Self) -> Vec<String> {
+    let mut keys: Vec<String> = self
+      .values
+      .iter()
+      .filter(|(key, value)| other.values.get(key.as_str()) != Some(value))
+      .map(|(key, _)| key.clone())
+      .collect();
+    keys.extend(
+      other
+        .values
+        .keys()
+        .filter(|key| !self.values.contains_key(key.as_str()))
+        .cloned(),
+    );
+    keys
+  }
+
+  /// The typed config, failing if any key is missing, has the wrong type or is invalid
+  pub fn config(&self) -> Result<RoxyConfig, RoxyCliError> {
+    self
+      .check()
+      .map_err(|reason| parse_error(self.path.clone(), reason))
+  }
+
+  /// Like `config`, with only the reason on failure
+  pub fn check(&self) -> Result<RoxyConfig, String> {
+    let config = RoxyConfig::from_values(self.values.clone())?;
+    config.validate()?;
+    Ok(config)
+  }
+
+  /// The typed config, with the defaults for keys that are missing or have
+  /// the wrong type
+  pub fn config_or_defaults(&self) -> RoxyConfig {
+    RoxyConfig::from_values(usable_values(&self.values)).unwrap_or_default()
+  }
+
+  /// Writes roxy.json back with only the changed values replaced
+  pub fn write(&self) -> Result<(), RoxyCliError> {
+    self.replace_contents(&self.contents)
+  }
+
+  /// Replaces roxy.json with `contents` as is. It is written next to roxy.json
+  /// first and then moved over it, so the server never reads half a file.
+  pub fn replace_contents(&self, contents: &str) -> Result<(), RoxyCliError> {
+    let temp_path = self.path.with_extension("json.tmp");
+    write_private(&temp_path, contents)
+      .map_err(RoxyCliError::io(format!("Failed to write {:?}", temp_path)))?;
+    fs::rename(&temp_path, &self.path).map_err(RoxyCliError::io(format!(
+      "Failed to replace {:?}",
+      self.path
+    )))
+  }
+}
+
+fn parse_error(path: PathBuf, reason: String) -> RoxyCliError {
+  RoxyCliError::ConfigParse { path, reason }
+}
+
+fn describe_error(err: serde_path_to_error::Error<serde_json::Error>) -> String {
+  let field = err.path().to_string();
+  if field == "." {
+    err.into_inner().to_string()
+  } else {
+    format!("`{}`: {}", field, err.into_inner())
+  }
+}
+
+/// The defaults, with every value from `values` that has the right type
+fn usable_values(values: &Map<String, Value>) -> Map<String, Value> {
+  let mut usable = default_values();
+  for (key, value) in values {
+    if !usable.contains_key(key) {
+      continue;
+    }
+    let mut candidate = usable.clone();
+    candidate.insert(key.clone(), value.clone());
+    if RoxyConfig::from_values(candidate.clone()).is_ok() {
+      usable = candidate;
+    }
+  }
+  usable
+}
+
+fn default_values() -> Map<String, Value> {
+  match serde_json::to_value(RoxyConfig::default()) {
+    Ok(Value::Object(values)) => values,
+    _ => Map::new(),
+  }
+}
+
+fn check_key(key: &str) -> Result<(), RoxyCliError> {
+  let keys = RoxyConfigFile::keys();
+  if keys.iter().any(|known| known == key) {
+    return Ok(());
+  }
+  Err(RoxyCliError::InvalidArgument(format!(
+    "Unknown key `{}`, expected one of: {}",
+    key,
+    keys.join(", ")
+  )))
+}
+
+/// Parses a command line value into the type of the key's default
+fn parse_value(key: &str, value: &str) -> Result<Value, String> {
+  match &default_values()[key] {
+    Value::Bool(_) => value
+      .parse::<bool>()
+      .map(Value::from)
+      .map_err(|_| format!("`{}` has to be true or false", key)),
+    Value::Number(_) => value
+      .parse::<u64>()
+      .map(Value::from)
+      .map_err(|_| format!("`{}` has to be a whole number", key))
+      .and_then(|number| match key {
+        "port" if !(1..=65535).contains(&number.as_u64().unwrap_or(0)) => {
+          Err(String::from("`port` has to be between 1 and 65535"))
+        }
+        _ => Ok(number),
+      }),
+    // Only the ssl paths default to null
+    Value::Null if value.is_empty() || value == "null" => Ok(Value::Null),
+    _ => Ok(Value::from(value)),
+  }
+}
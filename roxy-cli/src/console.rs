@@ -1,29 +1,181 @@
 use colored::{ColoredString, Colorize};
 use indicatif::{ProgressBar, ProgressStyle};
+use similar::{ChangeTag, TextDiff};
 use std::{
+  env,
   io::{self, Write},
+  path::Path,
+  process::Command,
   time::Duration,
 };
 
+use crate::error::RoxyCliError;
+use crate::report::{field, json_output, say};
+
+/// Spinners and progress bars stay hidden with --json
 pub fn start_simple_progress_bar(msg: &str) -> ProgressBar {
+  if json_output() {
+    return ProgressBar::hidden();
+  }
+
   let pb = ProgressBar::new_spinner();
   pb.set_message(msg.to_string());
+  set_spinner_style(&pb);
+  pb.enable_steady_tick(Duration::from_millis(100));
+  pb
+}
+
+pub fn set_spinner_style(pb: &ProgressBar) {
   pb.set_style(
     ProgressStyle::default_spinner()
       .template("{spinner:.green} {msg}")
       .expect("Failed to create spinner"),
   );
-  pb.enable_steady_tick(Duration::from_millis(100));
-  pb
 }
 
+/// Turns a spinner into a byte progress bar, `total` comes from Content-Length.
+pub fn set_download_style(pb: &ProgressBar, total: Option<u64>) {
+  let template = match total {
+    Some(total) => {
+      pb.set_length(total);
+      "{spinner:.green} {msg} [{bar:30.green}] {bytes}/{total_bytes} ({bytes_per_sec}, {eta})"
+    }
+    None => "{spinner:.green} {msg} {bytes} ({bytes_per_sec})",
+  };
+  pb.set_style(
+    ProgressStyle::default_bar()
+      .template(template)
+      .expect("Failed to create progress bar")
+      .progress_chars("=> "),
+  );
+}
+
+/// Asks on stderr with --json, so stdout stays parseable
 pub fn confirm(prompt: ColoredString) -> bool {
-  print!("{} [y/n]: ", prompt.bold().red());
-  io::stdout().flush().unwrap();
+  if json_output() {
+    eprint!("{} [y/n]: ", prompt.bold().red());
+    io::stderr().flush().ok();
+  } else {
+    print!("{} [y/n]: ", prompt.bold().red());
+    io::stdout().flush().ok();
+  }
 
+  // A closed or unreadable stdin counts as no
   let mut input = String::new();
-  io::stdin().read_line(&mut input).unwrap();
+  io::stdin().read_line(&mut input).ok();
 
   // Check if the user's response starts with 'y' or 'Y'
   input.trim().to_lowercase().starts_with('y')
 }
+
+/// Prints where prompts go: stdout, or stderr with --json
+fn print_prompt(text: String) {
+  if json_output() {
+    eprint!("{}", text);
+    io::stderr().flush().ok();
+  } else {
+    print!("{}", text);
+    io::stdout().flush().ok();
+  }
+}
+
+/// Asks for a value, `default` is used when the answer is empty
+pub fn ask(prompt: &str, default: Option<&str>) -> Result<String, RoxyCliError> {
+  let answer = match default {
+    Some(default) if !default.is_empty() => {
+      read_answer(format!("{} [{}]: ", prompt.bold(), default))?
+    }
+    _ => read_answer(format!("{}: ", prompt.bold()))?,
+  };
+  if answer.is_empty() {
+    Ok(default.unwrap_or_default().to_string())
+  } else {
+    Ok(answer)
+  }
+}
+
+/// Asks a yes or no question until it gets an answer
+pub fn ask_bool(prompt: &str, default: bool) -> Result<bool, RoxyCliError> {
+  let choices = if default { "Y/n" } else { "y/N" };
+  loop {
+    let answer = read_answer(format!("{} [{}]: ", prompt.bold(), choices))?;
+    match answer.to_lowercase().as_str() {
+      "" => return Ok(default),
+      "y" | "yes" => return Ok(true),
+      "n" | "no" => return Ok(false),
+      _ => reject_answer("Please answer y or n"),
+    }
+  }
+}
+
+fn read_answer(prompt: String) -> Result<String, RoxyCliError> {
+  print_prompt(prompt);
+
+  // Nobody is left to answer once stdin is closed
+  let mut input = String::new();
+  match io::stdin().read_line(&mut input) {
+    Ok(0) | Err(_) => {
+      print_prompt(String::from("\n"));
+      Err(RoxyCliError::Cancelled)
+    }
+    Ok(_) => Ok(input.trim().to_string()),
+  }
+}
+
+/// Tells why an answer to `ask` was not taken, before asking again
+pub fn reject_answer(reason: &str) {
+  print_prompt(format!("{}\n", reason.yellow()));
+}
+
+/// Opens `path` in $VISUAL or $EDITOR and waits for it to close
+pub fn open_editor(path: &Path) -> Result<(), RoxyCliError> {
+  #[cfg(target_os = "windows")]
+  let fallback = "notepad";
+  #[cfg(target_os = "linux")]
+  let fallback = "vi";
+
+  let editor = env::var("VISUAL")
+    .or_else(|_| env::var("EDITOR"))
+    .ok()
+    .filter(|editor| !editor.trim().is_empty())
+    .unwrap_or_else(|| fallback.to_string());
+  // e.g. EDITOR="code --wait"
+  let mut words = editor.split_whitespace();
+  let program = words.next().unwrap_or(fallback);
+
+  let status = Command::new(program)
+    .args(words)
+    .arg(path)
+    .status()
+    .map_err(|err| RoxyCliError::ChildProcess {
+      message: format!("Failed to run {}: {}", editor, err),
+      log: None,
+    })?;
+  if !status.success() {
+    return Err(RoxyCliError::ChildProcess {
+      message: format!("{} exited with {}", editor, status),
+      log: None,
+    });
+  }
+  Ok(())
+}
+
+/// Prints the changed lines with some context, reported as `diff` with --json
+pub fn print_diff(old: &str, new: &str) {
+  let diff = TextDiff::from_lines(old, new);
+  let mut unified = diff.unified_diff();
+  unified.context_radius(3);
+
+  for hunk in unified.iter_hunks() {
+    say(hunk.header().to_string().cyan());
+    for change in hunk.iter_changes() {
+      let line = change.value().trim_end_matches(['\r', '\n']);
+      match change.tag() {
+        ChangeTag::Delete => say(format!("-{}", line).red()),
+        ChangeTag::Insert => say(format!("+{}", line).green()),
+        ChangeTag::Equal => say(format!(" {}", line)),
+      }
+    }
+  }
+  field("diff", unified.to_string());
+}
@@ -0,0 +1,194 @@
+use colored::Colorize;
+use indicatif::ProgressBar;
+use reqwest::blocking::Client;
+use reqwest::header::{CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE, USER_AGENT};
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use crate::console::{set_download_style, set_spinner_style};
+use crate::error::RoxyCliError;
+use crate::journal::interrupted;
+use crate::utils::InstallInfo;
+
+const MAX_ATTEMPTS: u32 = 5;
+
+enum DownloadError {
+  /// Worth retrying, e.g. a dropped connection or a 5xx response
+  Transient(String),
+  Fatal(RoxyCliError),
+}
+
+/// Downloads `source` to `path`, or copies it if `source` is a local path.
+pub fn fetch_file(source: &str, path: &Path, pb: &ProgressBar) -> Result<(), RoxyCliError> {
+  if source.starts_with("http://") || source.starts_with("https://") {
+    download_file(source, path, pb)
+  } else {
+    fs::copy(source, path)
+      .map(|_| ())
+      .map_err(RoxyCliError::io(format!("Failed to copy {}", source)))
+  }
+}
+
+/// Streams `url` to `path`, showing byte progress on `pb`.
+///
+/// Data is written to a `.part` file in the roxy-cli downloads folder first.
+/// Transient failures are retried with exponential backoff, and both retries
+/// and later runs resume the `.part` file with an HTTP Range request.
+pub fn download_file(url: &str, path: &Path, pb: &ProgressBar) -> Result<(), RoxyCliError> {
+  let part_path = part_path(url)?;
+  if let Some(parent) = part_path.parent() {
+    fs::create_dir_all(parent).map_err(RoxyCliError::io("Failed to create downloads folder"))?;
+  }
+
+  let client = Client::builder()
+    .connect_timeout(Duration::from_secs(10))
+    .timeout(None)
+    .build()
+    .map_err(|err| RoxyCliError::Network(format!("Failed to create HTTP client: {}", err)))?;
+
+  let mut attempt = 1;
+  let result = loop {
+    match try_download(&client, url, &part_path, pb) {
+      Ok(()) => break Ok(()),
+      Err(DownloadError::Fatal(err)) => break Err(err),
+      Err(DownloadError::Transient(err)) if attempt >= MAX_ATTEMPTS => {
+        break Err(RoxyCliError::Network(format!(
+          "{} (gave up after {} attempts)",
+          err, attempt
+        )))
+      }
+      Err(DownloadError::Transient(err)) => {
+        let backoff = Duration::from_secs(2u64.pow(attempt - 1));
+        pb.println(format!(
+          "{}",
+          format!("{}, retrying in {}s...", err, backoff.as_secs()).yellow()
+        ));
+        thread::sleep(backoff);
+        attempt += 1;
+      }
+    }
+  };
+  set_spinner_style(pb);
+  result?;
+
+  // rename fails across filesystems, so fall back to copying
+  if fs::rename(&part_path, path).is_err() {
+    fs::copy(&part_path, path).map_err(RoxyCliError::io(format!("Failed to save {}", url)))?;
+    fs::remove_file(&part_path).ok();
+  }
+  fs::remove_file(validator_path(&part_path)).ok();
+
+  Ok(())
+}
+
+fn try_download(
+  client: &Client,
+  url: &str,
+  part_path: &Path,
+  pb: &ProgressBar,
+) -> Result<(), DownloadError> {
+  let mut existing = fs::metadata(part_path).map(|m| m.len()).unwrap_or(0);
+  let validator = fs::read_to_string(validator_path(part_path)).ok();
+
+  let mut request = client.get(url).header(USER_AGENT, "roxy-cli");
+  // Only resume if we can make sure the file did not change in the meantime,
+  // If-Range makes the server send the whole file otherwise
+  if let (true, Some(validator)) = (existing > 0, &validator) {
+    request = request
+      .header(RANGE, format!("bytes={}-", existing))
+      .header(IF_RANGE, validator.as_str());
+  }
+
+  let mut response = request
+    .send()
+    .map_err(|err| DownloadError::Transient(format!("Failed to download {}: {}", url, err)))?;
+
+  let status = response.status();
+  if status == StatusCode::RANGE_NOT_SATISFIABLE && existing > 0 {
+    // The .part file already holds the whole file
+    return Ok(());
+  }
+  if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+    return Err(DownloadError::Transient(format!(
+      "Failed to download {}: {}",
+      url, status
+    )));
+  }
+  if !status.is_success() {
+    return Err(DownloadError::Fatal(RoxyCliError::Network(format!(
+      "Failed to download {}: {}",
+      url, status
+    ))));
+  }
+
+  let resumed = status == StatusCode::PARTIAL_CONTENT
+    && response
+      .headers()
+      .get(CONTENT_RANGE)
+      .and_then(|v| v.to_str().ok())
+      .is_some_and(|v| v.starts_with(&format!("bytes {}-", existing)));
+
+  let io_error = |err: std::io::Error| {
+    DownloadError::Fatal(RoxyCliError::io(format!("Failed to write {:?}", part_path))(err))
+  };
+  let mut file = if resumed {
+    OpenOptions::new()
+      .append(true)
+      .open(part_path)
+      .map_err(io_error)?
+  } else {
+    existing = 0;
+    let validator = response
+      .headers()
+      .get(ETAG)
+      .or_else(|| response.headers().get(LAST_MODIFIED))
+      .and_then(|v| v.to_str().ok());
+    match validator {
+      Some(validator) => fs::write(validator_path(part_path), validator).map_err(io_error)?,
+      None => {
+        fs::remove_file(validator_path(part_path)).ok();
+      }
+    }
+    File::create(part_path).map_err(io_error)?
+  };
+
+  set_download_style(pb, response.content_length().map(|len| len + existing));
+  pb.set_position(existing);
+
+  let mut buffer = vec![0; 64 * 1024];
+  loop {
+    let read = response.read(&mut buffer).map_err(|err| {
+      DownloadError::Transient(format!("Connection to {} was interrupted: {}", url, err))
+    })?;
+    if read == 0 {
+      break;
+    }
+    if interrupted() {
+      return Err(DownloadError::Fatal(RoxyCliError::Cancelled));
+    }
+    file.write_all(&buffer[..read]).map_err(io_error)?;
+    pb.inc(read as u64);
+  }
+
+  Ok(())
+}
+
+/// Partial downloads are keyed by URL so they survive an aborted install,
+/// which deletes the app folder
+fn part_path(url: &str) -> Result<PathBuf, RoxyCliError> {
+  let hash = format!("{:x}", Sha256::digest(url.as_bytes()));
+  Ok(
+    InstallInfo::get_roxy_cli_path()?
+      .join("downloads")
+      .join(format!("{}.part", &hash[..16])),
+  )
+}
+
+fn validator_path(part_path: &Path) -> PathBuf {
+  part_path.with_extension("validator")
+}
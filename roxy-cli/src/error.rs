@@ -0,0 +1,183 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Exit codes, shown at the end of `roxy-cli --help`
+pub const EXIT_CODES: &str = "Exit codes:
+  0   Success
+  1   Other error
+  2   Invalid arguments
+  3   Roxy is not installed
+  4   Roxy is already installed
+  5   Roxy is not running
+  6   Cancelled
+  7   Network error
+  8   Checksum or signature verification failed
+  9   A child process (npm, node) failed
+  10  Roxy failed to start
+  11  Roxy failed to stop
+  12  Service manager error
+  13  A config file could not be parsed or is invalid
+  14  A file could not be read or written
+  15  A downloaded archive could not be extracted";
+
+/// Everything a command can fail with, each with an exit code scripts can
+/// rely on (see `EXIT_CODES`).
+#[derive(Debug)]
+pub enum RoxyCliError {
+  NotInstalled,
+  AlreadyInstalled,
+  NotRunning,
+  Cancelled,
+  InvalidArgument(String),
+  Network(String),
+  Verification(String),
+  /// `log` is where the full output went, if it was not shown
+  ChildProcess {
+    message: String,
+    log: Option<PathBuf>,
+  },
+  Start(String),
+  Stop(String),
+  ServiceManager(String),
+  ConfigParse {
+    path: PathBuf,
+    reason: String,
+  },
+  Io {
+    context: String,
+    source: io::Error,
+  },
+  Archive(String),
+  Other(String),
+}
+
+impl RoxyCliError {
+  /// e.g. `RoxyCliError::io(format!("Failed to delete {:?}", path))`
+  pub fn io(context: impl Into<String>) -> impl FnOnce(io::Error) -> Self {
+    let context = context.into();
+    move |source| Self::Io { context, source }
+  }
+
+  /// The same error with its message changed by `f`, e.g. to say which
+  /// install step it happened in
+  pub fn map_message(self, f: impl FnOnce(String) -> String) -> Self {
+    match self {
+      Self::InvalidArgument(message) => Self::InvalidArgument(f(message)),
+      Self::Network(message) => Self::Network(f(message)),
+      Self::Verification(message) => Self::Verification(f(message)),
+      Self::ChildProcess { message, log } => Self::ChildProcess {
+        message: f(message),
+        log,
+      },
+      Self::Start(message) => Self::Start(f(message)),
+      Self::Stop(message) => Self::Stop(f(message)),
+      Self::ServiceManager(message) => Self::ServiceManager(f(message)),
+      Self::Archive(message) => Self::Archive(f(message)),
+      Self::Other(message) => Self::Other(f(message)),
+      Self::Io { context, source } => Self::Io {
+        context: f(context),
+        source,
+      },
+      // Nothing to add to, their message is fixed or names the file already
+      err @ (Self::NotInstalled
+      | Self::AlreadyInstalled
+      | Self::NotRunning
+      | Self::Cancelled
+      | Self::ConfigParse { .. }) => err,
+    }
+  }
+
+  pub fn exit_code(&self) -> i32 {
+    match self {
+      Self::Other(_) => 1,
+      Self::InvalidArgument(_) => 2,
+      Self::NotInstalled => 3,
+      Self::AlreadyInstalled => 4,
+      Self::NotRunning => 5,
+      Self::Cancelled => 6,
+      Self::Network(_) => 7,
+      Self::Verification(_) => 8,
+      Self::ChildProcess { .. } => 9,
+      Self::Start(_) => 10,
+      Self::Stop(_) => 11,
+      Self::ServiceManager(_) => 12,
+      Self::ConfigParse { .. } => 13,
+      Self::Io { .. } => 14,
+      Self::Archive(_) => 15,
+    }
+  }
+
+  /// Reported as `kind` with --json. The names --json was introduced with are
+  /// kept, scripts may match on them.
+  pub fn kind(&self) -> &'static str {
+    match self {
+      Self::Other(_) => "general",
+      Self::InvalidArgument(_) => "invalid_argument",
+      Self::NotInstalled => "not_installed",
+      Self::AlreadyInstalled => "already_installed",
+      Self::NotRunning => "not_running",
+      Self::Cancelled => "cancelled",
+      Self::Network(_) => "network",
+      Self::Verification(_) => "verification",
+      Self::ChildProcess { .. } => "build",
+      Self::Start(_) => "start",
+      Self::Stop(_) => "stop",
+      Self::ServiceManager(_) => "service_manager",
+      Self::ConfigParse { .. } => "config",
+      Self::Io { .. } => "io",
+      Self::Archive(_) => "archive",
+    }
+  }
+
+  /// What the user can do about it
+  pub fn hint(&self) -> Option<String> {
+    let hint = match self {
+      Self::NotInstalled => "Run `roxy-cli install` to install it",
+      Self::AlreadyInstalled => "Run `roxy-cli update` to update it, or `roxy-cli uninstall` first",
+      Self::NotRunning => "Run `roxy-cli start` to start it",
+      Self::Network(_) => "Check your internet connection and try again",
+      Self::Verification(_) => {
+        "The download may be corrupted or tampered with, try again or check the checksum or key you passed"
+      }
+      Self::ChildProcess { log: Some(log), .. } => {
+        return Some(format!(
+          "The full output is in {:?}, run again with --verbose to see it live",
+          log
+        ))
+      }
+      Self::Start(_) => "Run `roxy-cli logs` to see what roxy logged",
+      Self::ServiceManager(_) => "Pick another one with --service-manager",
+      Self::Archive(_) => "The download may be incomplete or not a roxy or Node.js archive, try again",
+      Self::ConfigParse { path, .. } => {
+        return Some(format!("Fix {:?} by hand and try again", path))
+      }
+      _ => return None,
+    };
+    Some(hint.to_string())
+  }
+}
+
+impl fmt::Display for RoxyCliError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Self::NotInstalled => write!(f, "Roxy is not installed"),
+      Self::AlreadyInstalled => write!(f, "Roxy is already installed"),
+      Self::NotRunning => write!(f, "Roxy is not running"),
+      Self::Cancelled => write!(f, "Cancelled"),
+      Self::InvalidArgument(message)
+      | Self::Network(message)
+      | Self::Verification(message)
+      | Self::ChildProcess { message, .. }
+      | Self::Start(message)
+      | Self::Stop(message)
+      | Self::ServiceManager(message)
+      | Self::Archive(message)
+      | Self::Other(message) => write!(f, "{}", message),
+      Self::ConfigParse { path, reason } => write!(f, "Failed to parse {:?}: {}", path, reason),
+      Self::Io { context, source } => write!(f, "{}: {}", context, source),
+    }
+  }
+}
+
+impl std::error::Error for RoxyCliError {}
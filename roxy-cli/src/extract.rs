@@ -0,0 +1,604 @@
+use flate2::read::GzDecoder;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+use tar::EntryType;
+use zip::ZipArchive;
+
+use crate::error::RoxyCliError;
+
+/// Per-entry errors beyond this are only counted
+const MAX_REPORTED_ERRORS: usize = 10;
+
+/// Extracts a tar.gz or zip archive into `dest`.
+///
+/// Entries that would end up outside of `dest` are rejected: absolute paths,
+/// `..` components, symlinks pointing outside of `dest` and entries that would
+/// be written through a symlink.
+pub fn extract_archive(archive: &Path, dest: &Path) -> Result<(), RoxyCliError> {
+  let archive_name = archive
+    .file_name()
+    .map(|name| name.to_string_lossy().to_string())
+    .unwrap_or_default();
+  let open =
+    || File::open(archive).map_err(RoxyCliError::io(format!("Failed to open {}", archive_name)));
+
+  let mut magic = [0; 4];
+  // Too short to even have a header is no archive either
+  if open()?.read_exact(&mut magic).is_err() {
+    magic = [0; 4];
+  }
+
+  fs::create_dir_all(dest).map_err(RoxyCliError::io(format!("Failed to create {:?}", dest)))?;
+  let dest = normalize(dest);
+
+  let errors = match magic {
+    [0x50, 0x4b, 0x03, 0x04] => extract_zip(open()?, &dest),
+    [0x1f, 0x8b, _, _] => extract_tar_gz(open()?, &dest),
+    _ => Err(format!(
+      "{} is neither a tar.gz nor a zip archive",
+      archive_name
+    )),
+  }
+  .map_err(RoxyCliError::Archive)?;
+
+  if errors.is_empty() {
+    return Ok(());
+  }
+
+  let mut message = format!("Failed to extract {}:", archive_name);
+  for (entry, err) in errors.iter().take(MAX_REPORTED_ERRORS) {
+    message.push_str(&format!("\n  {}: {}", entry, err));
+  }
+  if errors.len() > MAX_REPORTED_ERRORS {
+    message.push_str(&format!(
+      "\n  ...and {} more",
+      errors.len() - MAX_REPORTED_ERRORS
+    ));
+  }
+  Err(RoxyCliError::Archive(message))
+}
+
+type EntryErrors = Vec<(String, String)>;
+
+fn extract_tar_gz(file: File, dest: &Path) -> Result<EntryErrors, String> {
+  let mut archive = tar::Archive::new(GzDecoder::new(file));
+  let entries = archive
+    .entries()
+    .map_err(|err| format!("Failed to read archive: {}", err))?;
+
+  let mut errors = Vec::new();
+  for entry in entries {
+    // A broken entry means the stream itself is corrupt, nothing after it can be read
+    let mut entry = entry.map_err(|err| format!("Failed to read archive: {}", err))?;
+    let name = entry
+      .path()
+      .map(|path| path.to_string_lossy().to_string())
+      .unwrap_or_else(|_| String::from("<invalid path>"));
+
+    if let Err(err) = unpack_tar_entry(&mut entry, dest) {
+      errors.push((name, err));
+    }
+  }
+
+  Ok(errors)
+}
+
+fn unpack_tar_entry<R: Read>(entry: &mut tar::Entry<R>, dest: &Path) -> Result<(), String> {
+  let path = entry.path().map_err(|err| err.to_string())?;
+  let target = match safe_join(dest, &path)? {
+    Some(target) => target,
+    None => return Ok(()),
+  };
+  prepare_parent(dest, &target)?;
+
+  match entry.header().entry_type() {
+    EntryType::Regular | EntryType::Directory | EntryType::Continuous => {}
+    EntryType::Symlink => {
+      let link_name = entry
+        .link_name()
+        .map_err(|err| err.to_string())?
+        .ok_or("symlink has no target")?;
+      check_symlink_target(dest, &target, &link_name)?;
+    }
+    EntryType::Link => {
+      let link_name = entry
+        .link_name()
+        .map_err(|err| err.to_string())?
+        .ok_or("hard link has no target")?;
+      let source = resolve_inside(dest, dest, &link_name)?;
+      if source == dest {
+        return Err(String::from("hard link has no target"));
+      }
+      // A hard link to a symlink is another symlink, resolved from where the link is
+      if fs::symlink_metadata(&source).is_ok_and(|metadata| metadata.file_type().is_symlink()) {
+        return Err(format!("hard link points to the symlink {:?}", link_name));
+      }
+      remove_existing(&target)?;
+      return fs::hard_link(source, &target).map_err(|err| err.to_string());
+    }
+    // Metadata entries, tar handles these internally
+    EntryType::XGlobalHeader | EntryType::XHeader | EntryType::GNULongName => return Ok(()),
+    other => return Err(format!("unsupported entry type {:?}", other)),
+  }
+
+  if !is_real_dir(&target) {
+    remove_existing(&target)?;
+  }
+  // Keeps the executable bits, but not setuid/setgid
+  entry.set_preserve_permissions(false);
+  entry
+    .unpack(&target)
+    .map(|_| ())
+    .map_err(|err| err.to_string())
+}
+
+fn extract_zip(file: File, dest: &Path) -> Result<EntryErrors, String> {
+  let mut archive =
+    ZipArchive::new(file).map_err(|err| format!("Failed to read archive: {}", err))?;
+
+  let mut errors = Vec::new();
+  for i in 0..archive.len() {
+    let mut entry = match archive.by_index(i) {
+      Ok(entry) => entry,
+      Err(err) => {
+        errors.push((format!("#{}", i), err.to_string()));
+        continue;
+      }
+    };
+    let name = entry.name().to_string();
+
+    if let Err(err) = unpack_zip_entry(&mut entry, dest) {
+      errors.push((name, err));
+    }
+  }
+
+  Ok(errors)
+}
+
+fn unpack_zip_entry(entry: &mut zip::read::ZipFile, dest: &Path) -> Result<(), String> {
+  let target = match safe_join(dest, Path::new(entry.name()))? {
+    Some(target) => target,
+    None => return Ok(()),
+  };
+  prepare_parent(dest, &target)?;
+
+  if entry.is_dir() {
+    if is_real_dir(&target) {
+      return Ok(());
+    }
+    remove_existing(&target)?;
+    return fs::create_dir(&target).map_err(|err| err.to_string());
+  }
+
+  let mode = entry.unix_mode();
+  const S_IFMT: u32 = 0o170000;
+  const S_IFLNK: u32 = 0o120000;
+  if mode.is_some_and(|mode| mode & S_IFMT == S_IFLNK) {
+    let mut link_name = String::new();
+    entry
+      .read_to_string(&mut link_name)
+      .map_err(|err| err.to_string())?;
+    check_symlink_target(dest, &target, Path::new(&link_name))?;
+    remove_existing(&target)?;
+    return create_symlink(Path::new(&link_name), &target);
+  }
+
+  remove_existing(&target)?;
+  let mut file = File::create(&target).map_err(|err| err.to_string())?;
+  io::copy(entry, &mut file).map_err(|err| err.to_string())?;
+
+  #[cfg(unix)]
+  if let Some(mode) = mode {
+    use std::os::unix::fs::PermissionsExt;
+    file
+      .set_permissions(fs::Permissions::from_mode(mode & 0o777))
+      .map_err(|err| err.to_string())?;
+  }
+
+  Ok(())
+}
+
+/// Joins an archive path onto `dest`, returning None for the archive root itself.
+fn safe_join(dest: &Path, path: &Path) -> Result<Option<PathBuf>, String> {
+  let mut relative = PathBuf::new();
+  for component in path.components() {
+    match component {
+      Component::Normal(part) => relative.push(part),
+      Component::CurDir => {}
+      Component::ParentDir => return Err(String::from("path contains '..'")),
+      Component::RootDir | Component::Prefix(_) => return Err(String::from("path is absolute")),
+    }
+  }
+
+  if relative.as_os_str().is_empty() {
+    Ok(None)
+  } else {
+    Ok(Some(dest.join(relative)))
+  }
+}
+
+/// Creates the parent folders of `target`, refusing to go through symlinks
+/// an earlier entry may have planted.
+fn prepare_parent(dest: &Path, target: &Path) -> Result<(), String> {
+  let parent = target.parent().unwrap_or(dest);
+  let mut current = dest.to_path_buf();
+  for part in parent
+    .strip_prefix(dest)
+    .unwrap_or(Path::new(""))
+    .components()
+  {
+    current.push(part);
+    match fs::symlink_metadata(&current) {
+      Ok(metadata) if metadata.file_type().is_symlink() => {
+        return Err(format!(
+          "would be written through the symlink {:?}",
+          current
+        ));
+      }
+      Ok(metadata) if !metadata.is_dir() => {
+        return Err(format!("{:?} is not a directory", current));
+      }
+      Ok(_) => {}
+      Err(_) => fs::create_dir(&current).map_err(|err| err.to_string())?,
+    }
+  }
+
+  Ok(())
+}
+
+fn check_symlink_target(dest: &Path, link: &Path, target: &Path) -> Result<(), String> {
+  if target.has_root() {
+    return Err(format!("symlink points to absolute path {:?}", target));
+  }
+  resolve_inside(dest, link.parent().unwrap_or(dest), target).map(|_| ())
+}
+
+/// Resolves `path` from the folder `base` one component at a time, checking
+/// each against what is on disk like `prepare_parent` does. Going through a
+/// symlink or above `dest` is refused, as is `..` after a name, which could
+/// still become a symlink through a later entry.
+fn resolve_inside(dest: &Path, base: &Path, path: &Path) -> Result<PathBuf, String> {
+  let mut current = base.to_path_buf();
+  let mut descended = false;
+  for component in path.components() {
+    match component {
+      Component::CurDir => {}
+      Component::ParentDir if descended => {
+        return Err(format!("{:?} goes back up with '..'", path));
+      }
+      Component::ParentDir => {
+        if current == dest || !current.pop() || !current.starts_with(dest) {
+          return Err(format!("{:?} points outside of the archive", path));
+        }
+      }
+      Component::Normal(part) => {
+        if fs::symlink_metadata(&current).is_ok_and(|metadata| metadata.file_type().is_symlink()) {
+          return Err(format!("{:?} goes through the symlink {:?}", path, current));
+        }
+        current.push(part);
+        descended = true;
+      }
+      Component::RootDir | Component::Prefix(_) => {
+        return Err(format!("{:?} is absolute", path));
+      }
+    }
+  }
+
+  Ok(current)
+}
+
+/// Like `Path::is_dir`, but without following symlinks.
+fn is_real_dir(path: &Path) -> bool {
+  fs::symlink_metadata(path)
+    .map(|metadata| metadata.is_dir())
+    .unwrap_or(false)
+}
+
+fn remove_existing(target: &Path) -> Result<(), String> {
+  match fs::symlink_metadata(target) {
+    Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(target),
+    Ok(_) => fs::remove_file(target),
+    Err(_) => Ok(()),
+  }
+  .map_err(|err| err.to_string())
+}
+
+#[cfg(unix)]
+fn create_symlink(link_name: &Path, target: &Path) -> Result<(), String> {
+  std::os::unix::fs::symlink(link_name, target).map_err(|err| err.to_string())
+}
+
+#[cfg(windows)]
+fn create_symlink(link_name: &Path, target: &Path) -> Result<(), String> {
+  std::os::windows::fs::symlink_file(link_name, target).map_err(|err| err.to_string())
+}
+
+/// Lexically resolves `.` and `..`, without touching the filesystem.
+fn normalize(path: &Path) -> PathBuf {
+  let mut result = PathBuf::new();
+  for component in path.components() {
+    match component {
+      Component::ParentDir => {
+        result.pop();
+      }
+      Component::CurDir => {}
+      other => result.push(other),
+    }
+  }
+  result
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use flate2::write::GzEncoder;
+  use flate2::Compression;
+  use std::env;
+  use std::io::{Cursor, Write};
+  use zip::write::FileOptions;
+  use zip::ZipWriter;
+
+  /// A scratch folder with `dest` to extract into. Everything an archive
+  /// manages to write outside of `dest` ends up next to it.
+  struct Scratch(PathBuf);
+
+  impl Scratch {
+    fn new(name: &str) -> Self {
+      let path = env::temp_dir().join(format!("roxy-cli-extract-{}-{}", name, std::process::id()));
+      fs::remove_dir_all(&path).ok();
+      fs::create_dir_all(path.join("dest")).unwrap();
+      Self(path)
+    }
+
+    fn dest(&self) -> PathBuf {
+      self.0.join("dest")
+    }
+
+    /// Writes `bytes` as the archive and extracts it into `dest`
+    fn extract(&self, file_name: &str, bytes: Vec<u8>) -> Result<(), String> {
+      let archive = self.0.join(file_name);
+      fs::write(&archive, bytes).unwrap();
+      extract_archive(&archive, &self.dest()).map_err(|err| err.to_string())
+    }
+  }
+
+  impl Drop for Scratch {
+    fn drop(&mut self) {
+      fs::remove_dir_all(&self.0).ok();
+    }
+  }
+
+  /// A tar.gz with entries of (path, type, link name, contents). Names are
+  /// written into the header as they are, `Header::set_path` would refuse
+  /// the ones these tests need.
+  fn tar_gz(entries: &[(&str, EntryType, &str, &[u8])]) -> Vec<u8> {
+    let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for (path, entry_type, link_name, contents) in entries {
+      let mut header = tar::Header::new_old();
+      header.as_old_mut().name[..path.len()].copy_from_slice(path.as_bytes());
+      header.as_old_mut().linkname[..link_name.len()].copy_from_slice(link_name.as_bytes());
+      header.set_entry_type(*entry_type);
+      header.set_size(contents.len() as u64);
+      header.set_mode(0o644);
+      header.set_cksum();
+      builder.append(&header, *contents).unwrap();
+    }
+    builder.into_inner().unwrap().finish().unwrap()
+  }
+
+  fn zip(build: impl FnOnce(&mut ZipWriter<Cursor<Vec<u8>>>)) -> Vec<u8> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    build(&mut writer);
+    writer.finish().unwrap().into_inner()
+  }
+
+  fn assert_rejected(result: Result<(), String>, reason: &str) {
+    let err = result.expect_err("archive was extracted");
+    assert!(
+      err.contains(reason),
+      "{:?} does not mention {:?}",
+      err,
+      reason
+    );
+  }
+
+  #[test]
+  fn safe_join_rejects_escaping_paths() {
+    let dest = Path::new("/data/app");
+    assert_eq!(
+      safe_join(dest, Path::new("./roxy/package.json")).unwrap(),
+      Some(dest.join("roxy/package.json"))
+    );
+    assert_eq!(safe_join(dest, Path::new("./")).unwrap(), None);
+    assert!(safe_join(dest, Path::new("roxy/../../etc/passwd")).is_err());
+    assert!(safe_join(dest, Path::new("/etc/passwd")).is_err());
+  }
+
+  #[test]
+  fn check_symlink_target_rejects_escaping_targets() {
+    let dest = Path::new("/data/app");
+    let link = dest.join("roxy/node_modules/.bin/tsc");
+    assert!(check_symlink_target(dest, &link, Path::new("../typescript/bin/tsc")).is_ok());
+    assert!(check_symlink_target(dest, &link, Path::new("../../../../../etc")).is_err());
+    assert!(check_symlink_target(dest, &link, Path::new("/etc")).is_err());
+  }
+
+  #[test]
+  fn extracts_regular_archive() {
+    let scratch = Scratch::new("regular");
+    let archive = tar_gz(&[
+      ("roxy/", EntryType::Directory, "", b""),
+      ("roxy/index.js", EntryType::Regular, "", b"main"),
+      ("roxy/start.js", EntryType::Symlink, "index.js", b""),
+      ("roxy/copy.js", EntryType::Link, "roxy/index.js", b""),
+    ]);
+    scratch.extract("roxy.tar.gz", archive).unwrap();
+    for file in ["index.js", "start.js", "copy.js"] {
+      assert_eq!(
+        fs::read(scratch.dest().join("roxy").join(file)).unwrap(),
+        b"main"
+      );
+    }
+  }
+
+  #[test]
+  fn tar_rejects_parent_paths() {
+    let scratch = Scratch::new("tar-parent");
+    let archive = tar_gz(&[("../evil", EntryType::Regular, "", b"evil")]);
+    assert_rejected(scratch.extract("roxy.tar.gz", archive), "'..'");
+    assert!(!scratch.0.join("evil").exists());
+  }
+
+  #[test]
+  fn tar_rejects_absolute_paths() {
+    let scratch = Scratch::new("tar-absolute");
+    let evil = scratch.0.join("evil");
+    let archive = tar_gz(&[(evil.to_str().unwrap(), EntryType::Regular, "", b"evil")]);
+    assert_rejected(scratch.extract("roxy.tar.gz", archive), "absolute");
+    assert!(!evil.exists());
+  }
+
+  #[test]
+  fn tar_rejects_escaping_symlinks() {
+    let scratch = Scratch::new("tar-symlink");
+    let archive = tar_gz(&[
+      ("up", EntryType::Symlink, "..", b""),
+      ("root", EntryType::Symlink, "/", b""),
+      ("up/evil", EntryType::Regular, "", b"evil"),
+    ]);
+    assert_rejected(
+      scratch.extract("roxy.tar.gz", archive),
+      "outside of the archive",
+    );
+    // up/evil still gets a real `up` folder of its own
+    assert!(is_real_dir(&scratch.dest().join("up")));
+    assert!(fs::symlink_metadata(scratch.dest().join("root")).is_err());
+    assert!(!scratch.0.join("evil").exists());
+  }
+
+  #[test]
+  fn tar_rejects_writing_through_symlinks() {
+    let scratch = Scratch::new("tar-through");
+    // Points inside the archive, so it is extracted, but nothing may be written through it
+    let archive = tar_gz(&[
+      ("here", EntryType::Symlink, ".", b""),
+      ("here/evil", EntryType::Regular, "", b"evil"),
+    ]);
+    assert_rejected(
+      scratch.extract("roxy.tar.gz", archive),
+      "through the symlink",
+    );
+    assert!(!scratch.dest().join("evil").exists());
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn tar_rejects_writing_through_planted_symlinks() {
+    let scratch = Scratch::new("tar-planted");
+    fs::create_dir(scratch.0.join("outside")).unwrap();
+    std::os::unix::fs::symlink(scratch.0.join("outside"), scratch.dest().join("node")).unwrap();
+    let archive = tar_gz(&[("node/evil", EntryType::Regular, "", b"evil")]);
+    assert_rejected(
+      scratch.extract("node.tar.gz", archive),
+      "through the symlink",
+    );
+    assert!(!scratch.0.join("outside/evil").exists());
+  }
+
+  #[test]
+  fn tar_rejects_escaping_hard_links() {
+    let scratch = Scratch::new("tar-hard-link");
+    let secret = scratch.0.join("secret");
+    fs::write(&secret, "secret").unwrap();
+    let archive = tar_gz(&[
+      ("parent", EntryType::Link, "../secret", b""),
+      ("absolute", EntryType::Link, secret.to_str().unwrap(), b""),
+    ]);
+    let err = scratch.extract("roxy.tar.gz", archive).unwrap_err();
+    assert!(
+      err.contains("parent: \"../secret\" points outside"),
+      "{}",
+      err
+    );
+    assert!(err.contains("is absolute"), "{}", err);
+    assert!(!scratch.dest().join("parent").exists());
+    assert!(!scratch.dest().join("absolute").exists());
+  }
+
+  #[test]
+  fn tar_rejects_links_through_extracted_symlinks() {
+    let scratch = Scratch::new("tar-chain");
+    fs::write(scratch.0.join("victim"), "victim").unwrap();
+    // Every link is harmless on its own, l3 only escapes once l1 and l2 exist
+    let archive = tar_gz(&[
+      ("d/", EntryType::Directory, "", b""),
+      ("l1", EntryType::Symlink, "d", b""),
+      ("d/l2", EntryType::Symlink, "..", b""),
+      ("l3", EntryType::Symlink, "l1/l2/..", b""),
+      ("h", EntryType::Link, "l3/victim", b""),
+    ]);
+    let err = scratch.extract("roxy.tar.gz", archive).unwrap_err();
+    assert!(err.contains("l3: "), "{}", err);
+    assert!(err.contains("h: "), "{}", err);
+    assert!(fs::symlink_metadata(scratch.dest().join("l3")).is_err());
+    assert!(fs::symlink_metadata(scratch.dest().join("h")).is_err());
+  }
+
+  #[test]
+  fn tar_rejects_hard_links_to_symlinks() {
+    let scratch = Scratch::new("tar-hard-symlink");
+    // a/up is the archive root, but a copy of it in the root would be its parent
+    let archive = tar_gz(&[
+      ("a/", EntryType::Directory, "", b""),
+      ("a/up", EntryType::Symlink, "..", b""),
+      ("up", EntryType::Link, "a/up", b""),
+    ]);
+    assert_rejected(
+      scratch.extract("roxy.tar.gz", archive),
+      "hard link points to the symlink",
+    );
+    assert!(fs::symlink_metadata(scratch.dest().join("up")).is_err());
+  }
+
+  #[test]
+  fn zip_rejects_parent_and_absolute_paths() {
+    let scratch = Scratch::new("zip-paths");
+    let evil = scratch.0.join("evil");
+    let archive = zip(|writer| {
+      writer
+        .start_file("../evil", FileOptions::default())
+        .unwrap();
+      writer.write_all(b"evil").unwrap();
+      writer
+        .start_file(evil.to_str().unwrap(), FileOptions::default())
+        .unwrap();
+      writer.write_all(b"evil").unwrap();
+    });
+    let err = scratch.extract("node.zip", archive).unwrap_err();
+    assert!(err.contains("path contains '..'"), "{}", err);
+    assert!(err.contains("path is absolute"), "{}", err);
+    assert!(!evil.exists());
+  }
+
+  #[test]
+  fn zip_rejects_escaping_symlinks() {
+    let scratch = Scratch::new("zip-symlink");
+    let archive = zip(|writer| {
+      writer
+        .add_symlink("up", "..", FileOptions::default())
+        .unwrap();
+      writer
+        .start_file("up/evil", FileOptions::default())
+        .unwrap();
+      writer.write_all(b"evil").unwrap();
+    });
+    assert_rejected(
+      scratch.extract("node.zip", archive),
+      "outside of the archive",
+    );
+    // up/evil still gets a real `up` folder of its own
+    assert!(is_real_dir(&scratch.dest().join("up")));
+    assert!(!scratch.0.join("evil").exists());
+  }
+}
@@ -0,0 +1,78 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::error::RoxyCliError;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Records Ctrl-C instead of exiting, so a running journal can roll back.
+/// Child processes get the signal too and fail their step on their own.
+pub fn catch_interrupts() -> Result<(), String> {
+  ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst))
+    .map_err(|err| format!("Failed to set Ctrl-C handler: {}", err))
+}
+
+pub fn interrupted() -> bool {
+  INTERRUPTED.load(Ordering::SeqCst)
+}
+
+type Undo = Box<dyn FnOnce() -> Result<(), String>>;
+
+/// A step that could not be reverted
+pub struct StepError {
+  pub step: String,
+  pub reason: String,
+}
+
+/// A step that failed, and what it failed with
+pub struct StepFailure {
+  pub step: String,
+  pub error: RoxyCliError,
+}
+
+/// A list of completed steps and how to revert each of them.
+pub struct Journal {
+  steps: Vec<(String, Undo)>,
+}
+
+impl Journal {
+  pub fn new() -> Self {
+    Self { steps: Vec::new() }
+  }
+
+  /// Runs `step`, remembering `undo` so `rollback` can revert it later.
+  /// `undo` is recorded before the step runs, as a failed step may have
+  /// done part of its work already.
+  pub fn run<T>(
+    &mut self,
+    name: &str,
+    step: impl FnOnce() -> Result<T, RoxyCliError>,
+    undo: impl FnOnce() -> Result<(), String> + 'static,
+  ) -> Result<T, StepFailure> {
+    let failure = |error: RoxyCliError| StepFailure {
+      step: name.to_string(),
+      error,
+    };
+
+    if interrupted() {
+      return Err(failure(RoxyCliError::Cancelled));
+    }
+    self.steps.push((name.to_string(), Box::new(undo)));
+
+    let result = step();
+    if interrupted() {
+      return Err(failure(RoxyCliError::Cancelled));
+    }
+    result.map_err(failure)
+  }
+
+  /// Reverts all recorded steps, newest first.
+  /// Returns the steps that could not be reverted.
+  pub fn rollback(self) -> Vec<StepError> {
+    self
+      .steps
+      .into_iter()
+      .rev()
+      .filter_map(|(step, undo)| undo().err().map(|reason| StepError { step, reason }))
+      .collect()
+  }
+}
@@ -0,0 +1,335 @@
+//! roxy.json may be written like the old config.jsonc, with `//` and `/* */`
+//! comments and trailing commas. Values are changed in place, so comments,
+//! key order and formatting stay as the operator wrote them.
+
+use serde_json::Value;
+use std::ops::Range;
+
+/// `contents` as plain JSON. Comments and trailing commas are replaced with
+/// spaces, so lines and columns in parse errors still match the file.
+pub fn strip(contents: &str) -> String {
+  let mut bytes = strip_comments(contents);
+
+  let mut i = 0;
+  while i < bytes.len() {
+    match bytes[i] {
+      b'"' => i = string_end(&bytes, i),
+      b',' => {
+        if matches!(bytes.get(skip_whitespace(&bytes, i + 1)), Some(b'}' | b']')) {
+          bytes[i] = b' ';
+        }
+        i += 1;
+      }
+      _ => i += 1,
+    }
+  }
+
+  // Only whole comments and commas were replaced, so this is still valid UTF-8
+  String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// `contents` with only its comments replaced with spaces
+fn strip_comments(contents: &str) -> Vec<u8> {
+  let mut bytes = contents.as_bytes().to_vec();
+
+  let mut i = 0;
+  while i < bytes.len() {
+    match (bytes[i], bytes.get(i + 1)) {
+      (b'"', _) => i = string_end(&bytes, i),
+      (b'/', Some(b'/')) => {
+        while i < bytes.len() && bytes[i] != b'\n' {
+          bytes[i] = b' ';
+          i += 1;
+        }
+      }
+      (b'/', Some(b'*')) => {
+        let end = bytes[i + 2..]
+          .windows(2)
+          .position(|window| window == b"*/")
+          .map(|position| i + 2 + position + 2)
+          .unwrap_or(bytes.len());
+        for byte in &mut bytes[i..end] {
+          if *byte != b'\n' {
+            *byte = b' ';
+          }
+        }
+        i = end;
+      }
+      _ => i += 1,
+    }
+  }
+  bytes
+}
+
+/// `contents` with the top level `key` set to `value`. A missing key is added
+/// after the last one, formatted like the keys before it.
+pub fn set(contents: &str, key: &str, value: &Value) -> Result<String, String> {
+  let result = splice(contents, key, value)?;
+  // Whatever the layout was, what gets written has to parse again
+  serde_json::from_str::<Value>(&strip(&result))
+    .map_err(|err| format!("setting `{}` would break the file: {}", key, err))?;
+  Ok(result)
+}
+
+fn splice(contents: &str, key: &str, value: &Value) -> Result<String, String> {
+  let stripped = strip(contents);
+  let object = Object::scan(stripped.as_bytes()).ok_or("expected an object")?;
+  let value = serde_json::to_string(value).map_err(|err| err.to_string())?;
+
+  let mut contents = contents.to_string();
+  // Like serde_json, the last of duplicate keys is the one that counts
+  if let Some(member) = object.members.iter().rev().find(|member| member.key == key) {
+    contents.replace_range(member.value.clone(), &value);
+    return Ok(contents);
+  }
+
+  let key = serde_json::to_string(key).map_err(|err| err.to_string())?;
+  let last = match object.members.last() {
+    Some(last) => last,
+    None => {
+      contents.insert_str(object.open + 1, &format!("{}: {}", key, value));
+      return Ok(contents);
+    }
+  };
+  // A comment may sit between the value and its comma, e.g. `"a": 1 /* b */,`
+  let without_comments = strip_comments(&contents);
+  let has_comma =
+    without_comments.get(skip_whitespace(&without_comments, last.value.end)) == Some(&b',');
+  let first_line = line_start(&contents, object.members[0].key_start);
+  let indent = &contents[first_line..object.members[0].key_start];
+
+  // Keys on their own lines, with `}` on a line of its own
+  let close_line = line_start(&contents, object.close);
+  let pretty = first_line > object.open
+    && close_line > last.value.end
+    && indent.trim().is_empty()
+    && contents[close_line..object.close].trim().is_empty();
+  if pretty {
+    let newline = if contents.contains("\r\n") {
+      "\r\n"
+    } else {
+      "\n"
+    };
+    let member = format!(
+      "{}{}: {}{}{}",
+      indent,
+      key,
+      value,
+      if has_comma { "," } else { "" },
+      newline
+    );
+    contents.insert_str(close_line, &member);
+    if !has_comma {
+      contents.insert(last.value.end, ',');
+    }
+  } else if first_line > object.open {
+    contents.insert_str(last.value.end, &format!(", {}: {}", key, value));
+  } else {
+    contents.insert_str(last.value.end, &format!(",{}:{}", key, value));
+  }
+  Ok(contents)
+}
+
+/// The top level object, as byte offsets into the file
+struct Object {
+  open: usize,
+  members: Vec<Member>,
+  close: usize,
+}
+
+struct Member {
+  key: String,
+  /// Where the key's opening quote is
+  key_start: usize,
+  value: Range<usize>,
+}
+
+impl Object {
+  /// Expects JSON that serde_json already parsed, after `strip`
+  fn scan(bytes: &[u8]) -> Option<Self> {
+    let open = skip_whitespace(bytes, 0);
+    if bytes.get(open) != Some(&b'{') {
+      return None;
+    }
+
+    let mut members = Vec::new();
+    let mut i = skip_whitespace(bytes, open + 1);
+    while bytes.get(i) == Some(&b'"') {
+      let key_end = string_end(bytes, i);
+      let key = serde_json::from_slice(&bytes[i..key_end]).ok()?;
+      let colon = skip_whitespace(bytes, key_end);
+      if bytes.get(colon) != Some(&b':') {
+        return None;
+      }
+      let value_start = skip_whitespace(bytes, colon + 1);
+      let value_end = value_end(bytes, value_start);
+      members.push(Member {
+        key,
+        key_start: i,
+        value: value_start..value_end,
+      });
+
+      i = skip_whitespace(bytes, value_end);
+      if bytes.get(i) == Some(&b',') {
+        i = skip_whitespace(bytes, i + 1);
+      }
+    }
+
+    if bytes.get(i) != Some(&b'}') {
+      return None;
+    }
+    Some(Self {
+      open,
+      members,
+      close: i,
+    })
+  }
+}
+
+/// Offset just past the string starting at `start`
+fn string_end(bytes: &[u8], start: usize) -> usize {
+  let mut i = start + 1;
+  while i < bytes.len() {
+    match bytes[i] {
+      b'\\' => i += 2,
+      b'"' => return i + 1,
+      _ => i += 1,
+    }
+  }
+  bytes.len()
+}
+
+/// Offset just past the value starting at `start`
+fn value_end(bytes: &[u8], start: usize) -> usize {
+  match bytes.get(start) {
+    Some(b'"') => string_end(bytes, start),
+    Some(b'{' | b'[') => {
+      let mut depth = 0;
+      let mut i = start;
+      while i < bytes.len() {
+        match bytes[i] {
+          b'"' => {
+            i = string_end(bytes, i);
+            continue;
+          }
+          b'{' | b'[' => depth += 1,
+          b'}' | b']' => {
+            depth -= 1;
+            if depth == 0 {
+              return i + 1;
+            }
+          }
+          _ => {}
+        }
+        i += 1;
+      }
+      bytes.len()
+    }
+    _ => {
+      let mut i = start;
+      while i < bytes.len() && !b",}] \t\r\n".contains(&bytes[i]) {
+        i += 1;
+      }
+      i
+    }
+  }
+}
+
+fn skip_whitespace(bytes: &[u8], start: usize) -> usize {
+  let mut i = start;
+  while i < bytes.len() && bytes[i].is_ascii_whitespace() {
+    i += 1;
+  }
+  i
+}
+
+/// Offset of the start of the line `offset` is on
+fn line_start(contents: &str, offset: usize) -> usize {
+  contents[..offset]
+    .rfind('\n')
+    .map_or(0, |newline| newline + 1)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn strip_keeps_comments_in_strings() {
+    let contents = "{\n  \"url\": \"http://a/*b*/c//d\", // e\n  /* f */ \"g\": 1,\n}";
+    let stripped = strip(contents);
+    assert_eq!(stripped.len(), contents.len());
+    assert_eq!(
+      serde_json::from_str::<Value>(&stripped).unwrap(),
+      json!({ "url": "http://a/*b*/c//d", "g": 1 })
+    );
+  }
+
+  #[test]
+  fn set_replaces_value_and_keeps_comments() {
+    let contents = "{\n  // Where roxy listens\n  \"port\": 80, // not 443\n  \"url\": \"x\"\n}";
+    assert_eq!(
+      set(contents, "port", &json!(8080)).unwrap(),
+      "{\n  // Where roxy listens\n  \"port\": 8080, // not 443\n  \"url\": \"x\"\n}"
+    );
+  }
+
+  #[test]
+  fn set_adds_missing_key_to_pretty_file() {
+    assert_eq!(
+      set("{\n  \"a\": 1\n}\n", "b", &json!(2)).unwrap(),
+      "{\n  \"a\": 1,\n  \"b\": 2\n}\n"
+    );
+  }
+
+  #[test]
+  fn set_adds_missing_key_to_compact_file() {
+    assert_eq!(
+      set("{\"a\":1}", "b", &json!(2)).unwrap(),
+      "{\"a\":1,\"b\":2}"
+    );
+    assert_eq!(set("{}", "b", &json!(2)).unwrap(), "{\"b\": 2}");
+  }
+
+  #[test]
+  fn set_keeps_trailing_commas() {
+    assert_eq!(
+      set("{\n  \"a\": 1,\n}", "b", &json!(2)).unwrap(),
+      "{\n  \"a\": 1,\n  \"b\": 2,\n}"
+    );
+  }
+
+  #[test]
+  fn set_finds_comma_after_comment() {
+    assert_eq!(
+      set("{\n  \"a\": 1 /* c */,\n}", "b", &json!(2)).unwrap(),
+      "{\n  \"a\": 1 /* c */,\n  \"b\": 2,\n}"
+    );
+    assert_eq!(
+      set("{\n  \"a\": 1 // c\n}", "b", &json!(2)).unwrap(),
+      "{\n  \"a\": 1, // c\n  \"b\": 2\n}"
+    );
+  }
+
+  #[test]
+  fn set_changes_last_duplicate_key() {
+    assert_eq!(
+      set("{\"a\": 1, \"a\": 2}", "a", &json!(3)).unwrap(),
+      "{\"a\": 1, \"a\": 3}"
+    );
+  }
+
+  #[test]
+  fn set_keeps_crlf() {
+    assert_eq!(
+      set("{\r\n  \"a\": 1\r\n}\r\n", "b", &json!(2)).unwrap(),
+      "{\r\n  \"a\": 1,\r\n  \"b\": 2\r\n}\r\n"
+    );
+  }
+
+  #[test]
+  fn set_rejects_non_object() {
+    assert!(set("[1]", "a", &json!(1)).is_err());
+  }
+}
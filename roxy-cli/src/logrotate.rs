@@ -0,0 +1,89 @@
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::utils::{InstallInfo, LogRotation};
+
+/// Rotates every log roxy and roxy-cli write to the data path that is due.
+/// Returns the logs that could not be rotated. See `LogRotation` for when this runs.
+pub fn rotate_logs(install_info: &InstallInfo) -> Vec<String> {
+  [
+    install_info.server_out_log_path(),
+    install_info.server_err_log_path(),
+    install_info.roxy_log_path(),
+    install_info.supervisor_log_path(),
+  ]
+  .iter()
+  .filter_map(|path| rotate_if_due(path, &install_info.log_rotation).err())
+  .collect()
+}
+
+/// Rotates `path` if it grew past `max_size_mb` or was last rotated more than
+/// `max_age_days` ago.
+///
+/// The log is copied to `<name>.1.gz` and then truncated in place, instead of
+/// being renamed, since the server keeps writing to it through an open file.
+/// Older archives move up by one and anything past `keep` is deleted.
+fn rotate_if_due(path: &Path, rotation: &LogRotation) -> Result<(), String> {
+  let metadata = match fs::metadata(path) {
+    Ok(metadata) if metadata.len() > 0 => metadata,
+    _ => return Ok(()),
+  };
+
+  let too_big = metadata.len() > rotation.max_size_mb * 1024 * 1024;
+  // The newest archive marks the last rotation, the log itself the first write
+  let since = fs::metadata(archive_path(path, 1))
+    .and_then(|archive| archive.modified())
+    .or_else(|_| metadata.created())
+    .ok();
+  let too_old = since
+    .and_then(|since| SystemTime::now().duration_since(since).ok())
+    .is_some_and(|age| age > Duration::from_secs(rotation.max_age_days * 24 * 60 * 60));
+
+  if too_big || too_old {
+    rotate(path, rotation.keep).map_err(|err| format!("Failed to rotate {:?}: {}", path, err))
+  } else {
+    Ok(())
+  }
+}
+
+fn rotate(path: &Path, keep: usize) -> io::Result<()> {
+  if keep > 0 {
+    let oldest = archive_path(path, keep);
+    if oldest.exists() {
+      fs::remove_file(oldest)?;
+    }
+    for i in (1..keep).rev() {
+      let archive = archive_path(path, i);
+      if archive.exists() {
+        fs::rename(&archive, archive_path(path, i + 1))?;
+      }
+    }
+
+    let mut encoder = GzEncoder::new(File::create(archive_path(path, 1))?, Compression::default());
+    io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?;
+  }
+
+  // Writers open their logs in append mode, so they continue at the new end
+  OpenOptions::new().write(true).open(path)?.set_len(0)
+}
+
+/// Existing archives of `path`, oldest first
+pub fn archives(path: &Path) -> Vec<PathBuf> {
+  let mut archives: Vec<PathBuf> = (1..)
+    .map(|i| archive_path(path, i))
+    .take_while(|archive| archive.exists())
+    .collect();
+  archives.reverse();
+  archives
+}
+
+fn archive_path(path: &Path, index: usize) -> PathBuf {
+  let mut name = path.file_name().unwrap_or_default().to_os_string();
+  name.push(format!(".{}.gz", index));
+  path.with_file_name(name)
+}
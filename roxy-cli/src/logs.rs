@@ -0,0 +1,396 @@
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use colored::Colorize;
+use flate2::read::GzDecoder;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::thread;
+
+use crate::args::{LogLevel, LogsCommand};
+use crate::logrotate::archives;
+use crate::report::{field, json_output};
+
+/// Entries shown when following without -n, like `tail -f`
+const DEFAULT_FOLLOW_LINES: usize = 10;
+
+/// One entry of roxy.log, written by the server's Logger as
+/// `[2023-07-01T12:00:00.000Z] [Warn] message`
+#[derive(Serialize)]
+pub struct LogEntry {
+  pub timestamp: DateTime<Utc>,
+  pub level: LogLevel,
+  /// May span several lines, e.g. for stack traces
+  pub message: String,
+}
+
+pub struct LogFilter {
+  level: Option<LogLevel>,
+  since: Option<DateTime<Utc>>,
+  until: Option<DateTime<Utc>>,
+  grep: Option<Regex>,
+}
+
+impl LogFilter {
+  pub fn from_command(props: &LogsCommand) -> Self {
+    Self {
+      level: props.level,
+      since: props.since,
+      until: props.until,
+      grep: props.grep.clone(),
+    }
+  }
+
+  fn matches(&self, entry: &LogEntry) -> bool {
+    self.level.is_none_or(|level| entry.level >= level)
+      && self.since.is_none_or(|since| entry.timestamp >= since)
+      && self.until.is_none_or(|until| entry.timestamp <= until)
+      && self
+        .grep
+        .as_ref()
+        .is_none_or(|grep| grep.is_match(&entry.message))
+  }
+}
+
+/// Prints the entries of the log at `path` that match, then keeps printing
+/// new ones if following.
+pub fn print_logs(path: &Path, props: &LogsCommand) -> Result<(), String> {
+  let filter = LogFilter::from_command(props);
+  let count = props.lines.or(props.follow.then_some(DEFAULT_FOLLOW_LINES));
+
+  // Older entries may already be rotated away
+  let mut contents = String::new();
+  if props.since.is_some() {
+    for archive in archives(path) {
+      let file =
+        File::open(&archive).map_err(|err| format!("Failed to open {:?}: {}", archive, err))?;
+      GzDecoder::new(file)
+        .read_to_string(&mut contents)
+        .map_err(|err| format!("Failed to read {:?}: {}", archive, err))?;
+    }
+  }
+  let mut file = File::open(path).map_err(|err| format!("Failed to open {:?}: {}", path, err))?;
+  file
+    .read_to_string(&mut contents)
+    .map_err(|err| format!("Failed to read {:?}: {}", path, err))?;
+
+  let mut shown = VecDeque::new();
+  for entry in parse_entries(&contents).filter(|entry| filter.matches(entry)) {
+    shown.push_back(entry);
+    if count.is_some_and(|count| shown.len() > count) {
+      shown.pop_front();
+    }
+  }
+  if json_output() && !props.follow {
+    field("entries", shown);
+    return Ok(());
+  }
+  for entry in &shown {
+    print_entry(entry);
+  }
+
+  if props.follow {
+    follow(file, &filter)?;
+  }
+  Ok(())
+}
+
+/// Prints entries as they are appended, until roxy-cli is interrupted
+fn follow(mut file: File, filter: &LogFilter) -> Result<(), String> {
+  let read_error = |err: std::io::Error| format!("Failed to read roxy.log: {}", err);
+  let mut position = file.stream_position().map_err(read_error)?;
+
+  loop {
+    thread::sleep(std::time::Duration::from_millis(250));
+
+    let len = file.metadata().map_err(read_error)?.len();
+    if len < position {
+      // Truncated by log rotation
+      position = 0;
+    }
+    if len == position {
+      continue;
+    }
+
+    file.seek(SeekFrom::Start(position)).map_err(read_error)?;
+    let mut appended = String::new();
+    file.read_to_string(&mut appended).map_err(read_error)?;
+    position = file.stream_position().map_err(read_error)?;
+
+    // The Logger writes each entry in one go, so nothing is split across reads
+    for entry in parse_entries(&appended).filter(|entry| filter.matches(entry)) {
+      print_entry(&entry);
+    }
+  }
+}
+
+/// Splits a log into entries. Lines without a header belong to the entry before.
+pub fn parse_entries(contents: &str) -> impl Iterator<Item = LogEntry> + '_ {
+  let mut lines = contents.lines().peekable();
+  std::iter::from_fn(move || loop {
+    let line = lines.next()?;
+    let mut entry = match parse_header(line) {
+      Some(entry) => entry,
+      None => continue,
+    };
+
+    while let Some(next) = lines.peek() {
+      if parse_header(next).is_some() {
+        break;
+      }
+      if !next.is_empty() {
+        entry.message.push('\n');
+        entry.message.push_str(next);
+      }
+      lines.next();
+    }
+    return Some(entry);
+  })
+}
+
+fn parse_header(line: &str) -> Option<LogEntry> {
+  let rest = line.strip_prefix('[')?;
+  let (timestamp, rest) = rest.split_once("] [")?;
+  let (level, message) = match rest.split_once("] ") {
+    Some(parts) => parts,
+    None => (rest.strip_suffix(']')?, ""),
+  };
+
+  Some(LogEntry {
+    timestamp: DateTime::parse_from_rfc3339(timestamp)
+      .ok()?
+      .with_timezone(&Utc),
+    level: match level {
+      "Log" => LogLevel::Log,
+      "Warn" => LogLevel::Warn,
+      "Err" => LogLevel::Err,
+      _ => return None,
+    },
+    message: message.to_string(),
+  })
+}
+
+/// Prints an entry, as a JSON line with --json
+fn print_entry(entry: &LogEntry) {
+  if json_output() {
+    println!("{}", serde_json::to_string(entry).unwrap_or_default());
+    return;
+  }
+
+  let timestamp = entry
+    .timestamp
+    .with_timezone(&Local)
+    .format("%Y-%m-%d %H:%M:%S")
+    .to_string();
+  let message = match entry.level {
+    LogLevel::Log => entry.message.normal(),
+    LogLevel::Warn => entry.message.yellow(),
+    LogLevel::Err => entry.message.red(),
+  };
+  println!("{} {}", timestamp.dimmed(), message);
+}
+
+/// Parses `--since`/`--until`: a duration ago like 30m, 2h or 7d, a local date
+/// or date and time, or an RFC 3339 timestamp.
+pub fn parse_time(value: &str) -> Result<DateTime<Utc>, String> {
+  if let Some(ago) = parse_duration(value) {
+    return Ok(Utc::now() - ago);
+  }
+  if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+    return Ok(time.with_timezone(&Utc));
+  }
+
+  let local = ["%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M"]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+    .or_else(|| {
+      NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+    })
+    .ok_or_else(|| {
+      format!(
+        "'{}' is not a time, use e.g. 30m, 2h, 7d, 2023-07-01 or 2023-07-01 12:00:00",
+        value
+      )
+    })?;
+
+  Local
+    .from_local_datetime(&local)
+    .earliest()
+    .map(|time| time.with_timezone(&Utc))
+    .ok_or_else(|| format!("'{}' does not exist in the local timezone", value))
+}
+
+fn parse_duration(value: &str) -> Option<Duration> {
+  // The unit may be any character, so split on its boundary rather than a byte
+  let (unit_start, _) = value.char_indices().next_back()?;
+  let (amount, unit) = value.split_at(unit_start);
+  let amount: i64 = amount.parse().ok()?;
+  match unit {
+    "s" => Duration::try_seconds(amount),
+    "m" => Duration::try_minutes(amount),
+    "h" => Duration::try_hours(amount),
+    "d" => Duration::try_days(amount),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const LOG: &str = "\
+[2023-07-01T12:00:00.000Z] [Log] started
+[2023-07-01T12:05:00.000Z] [Warn] slow request
+[2023-07-01T12:10:00.000Z] [Err] Error: boom
+    at handler (index.js:1:1)
+
+    at main (index.js:2:2)
+[2023-07-01T12:15:00.000Z] [Log]
+";
+
+  fn time(value: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(value)
+      .unwrap()
+      .with_timezone(&Utc)
+  }
+
+  fn filter(level: Option<LogLevel>, since: Option<&str>, until: Option<&str>) -> LogFilter {
+    LogFilter {
+      level,
+      since: since.map(time),
+      until: until.map(time),
+      grep: None,
+    }
+  }
+
+  fn matching(filter: &LogFilter) -> Vec<String> {
+    parse_entries(LOG)
+      .filter(|entry| filter.matches(entry))
+      .map(|entry| entry.message)
+      .collect()
+  }
+
+  #[test]
+  fn parse_header_reads_timestamp_level_and_message() {
+    let entry = parse_header("[2023-07-01T12:05:00.000Z] [Warn] slow request").unwrap();
+    assert_eq!(entry.timestamp, time("2023-07-01T12:05:00Z"));
+    assert_eq!(entry.level, LogLevel::Warn);
+    assert_eq!(entry.message, "slow request");
+
+    let empty = parse_header("[2023-07-01T12:05:00.000Z] [Err]").unwrap();
+    assert_eq!(empty.message, "");
+  }
+
+  #[test]
+  fn parse_header_rejects_other_lines() {
+    assert!(parse_header("    at main (index.js:2:2)").is_none());
+    assert!(parse_header("[not a time] [Log] x").is_none());
+    assert!(parse_header("[2023-07-01T12:05:00.000Z] [Info] x").is_none());
+    assert!(parse_header("[2023-07-01T12:05:00.000Z] x").is_none());
+    assert!(parse_header("").is_none());
+  }
+
+  #[test]
+  fn parse_entries_joins_continuation_lines() {
+    let entries: Vec<_> = parse_entries(LOG).collect();
+    assert_eq!(entries.len(), 4);
+    assert_eq!(entries[0].message, "started");
+    assert_eq!(
+      entries[2].message,
+      "Error: boom\n    at handler (index.js:1:1)\n    at main (index.js:2:2)"
+    );
+    assert_eq!(entries[3].level, LogLevel::Log);
+    assert_eq!(entries[3].message, "");
+  }
+
+  #[test]
+  fn parse_entries_skips_lines_before_the_first_header() {
+    let entries: Vec<_> = parse_entries("leftover\n[2023-07-01T12:00:00Z] [Log] a\n").collect();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].message, "a");
+  }
+
+  #[test]
+  fn level_filter_keeps_that_level_and_above() {
+    assert_eq!(matching(&filter(Some(LogLevel::Log), None, None)).len(), 4);
+    assert_eq!(
+      matching(&filter(Some(LogLevel::Warn), None, None)),
+      [
+        "slow request",
+        "Error: boom\n    at handler (index.js:1:1)\n    at main (index.js:2:2)"
+      ]
+    );
+    assert_eq!(matching(&filter(Some(LogLevel::Err), None, None)).len(), 1);
+  }
+
+  #[test]
+  fn since_and_until_are_inclusive() {
+    let window = filter(
+      None,
+      Some("2023-07-01T12:05:00Z"),
+      Some("2023-07-01T12:10:00Z"),
+    );
+    assert_eq!(matching(&window).len(), 2);
+    assert_eq!(matching(&window)[0], "slow request");
+
+    let after = filter(None, Some("2023-07-01T12:15:00.001Z"), None);
+    assert!(matching(&after).is_empty());
+    let before = filter(None, None, Some("2023-07-01T11:59:59Z"));
+    assert!(matching(&before).is_empty());
+  }
+
+  #[test]
+  fn parse_time_reads_durations() {
+    let before = Utc::now();
+    let ago = parse_time("2h").unwrap();
+    let after = Utc::now();
+    assert!(before - Duration::hours(2) <= ago && ago <= after - Duration::hours(2));
+
+    assert_eq!(parse_duration("30m"), Some(Duration::minutes(30)));
+    assert_eq!(parse_duration("7d"), Some(Duration::days(7)));
+    assert_eq!(parse_duration("15s"), Some(Duration::seconds(15)));
+    assert_eq!(parse_duration("5w"), None);
+    assert_eq!(parse_duration("m"), None);
+    assert_eq!(parse_duration(""), None);
+  }
+
+  #[test]
+  fn parse_time_rejects_multibyte_units() {
+    assert_eq!(parse_duration("5é"), None);
+    assert_eq!(parse_duration("é"), None);
+    assert!(parse_time("5é").unwrap_err().contains("'5é' is not a time"));
+  }
+
+  #[test]
+  fn parse_time_reads_timestamps_and_dates() {
+    assert_eq!(
+      parse_time("2023-07-01T12:00:00+02:00").unwrap(),
+      time("2023-07-01T10:00:00Z")
+    );
+
+    let local = |value: &str| {
+      Local
+        .from_local_datetime(&NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").unwrap())
+        .earliest()
+        .unwrap()
+        .with_timezone(&Utc)
+    };
+    assert_eq!(
+      parse_time("2023-07-01").unwrap(),
+      local("2023-07-01 00:00:00")
+    );
+    assert_eq!(
+      parse_time("2023-07-01 12:30").unwrap(),
+      local("2023-07-01 12:30:00")
+    );
+    assert_eq!(
+      parse_time("2023-07-01T12:30:15").unwrap(),
+      local("2023-07-01 12:30:15")
+    );
+    assert!(parse_time("yesterday").is_err());
+  }
+}
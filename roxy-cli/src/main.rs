@@ -1,9 +1,27 @@
 mod args;
+mod build;
+mod config;
 mod console;
+mod download;
+mod error;
+mod extract;
+mod journal;
+mod jsonc;
+mod logrotate;
+mod logs;
 mod ops;
+mod report;
+mod server;
+mod service;
+mod setup;
+mod supervisor;
 mod utils;
-use args::{AutostartSubcommand, RoxyCliArgs, RoxySubcommand};
+mod verify;
+mod watchdog;
+use args::{AutostartSubcommand, ConfigSubcommand, OutputFormat, RoxyCliArgs, RoxySubcommand};
 use clap::Parser;
+use std::process;
+use std::time::Duration;
 
 // Paths:
 // roxy-cli "memory" file:
@@ -16,21 +34,72 @@ use clap::Parser;
 
 fn main() {
   let args = RoxyCliArgs::parse();
+  report::set_json_output(args.json || args.format == OutputFormat::Json);
+  let command = command_name(&args.command);
 
-  match args.command {
+  let result = match args.command {
     RoxySubcommand::Status => ops::status(),
-    RoxySubcommand::Install(props) => ops::install(props.path),
+    RoxySubcommand::Install(props) => ops::install(*props),
     RoxySubcommand::Uninstall => ops::uninstall(),
-    RoxySubcommand::Update => ops::update(),
-    RoxySubcommand::Start => ops::start(),
-    RoxySubcommand::Restart => ops::restart(),
+    RoxySubcommand::Setup(props) => ops::setup(props),
+    RoxySubcommand::Update(props) => ops::update(props),
+    RoxySubcommand::Start(props) => ops::start(Duration::from_secs(props.timeout)),
+    RoxySubcommand::Restart(props) => ops::restart(Duration::from_secs(props.timeout)),
     RoxySubcommand::Stop => ops::stop(),
+    RoxySubcommand::Run => ops::run(),
+    RoxySubcommand::Supervise(props) => ops::supervise(
+      props.max_restarts,
+      props.watchdog.then_some(props.watchdog_options),
+    ),
+    RoxySubcommand::Watchdog(options) => ops::watchdog(options),
     RoxySubcommand::Autostart(sc) => match sc.command {
-      AutostartSubcommand::Enable => ops::autostart_enable(),
-      AutostartSubcommand::Disable => ops::autostart_disable(),
-      AutostartSubcommand::Status => ops::autostart_status(),
+      AutostartSubcommand::Enable => ops::autostart_enable(sc.service_manager),
+      AutostartSubcommand::Disable => ops::autostart_disable(sc.service_manager),
+      AutostartSubcommand::Status => ops::autostart_status(sc.service_manager),
     },
-    RoxySubcommand::Config => ops::config(),
-    RoxySubcommand::Logs => ops::logs(),
+    RoxySubcommand::Config(sc) => match sc.command {
+      ConfigSubcommand::Get { key } => ops::config_get(&key),
+      ConfigSubcommand::Set { key, value } => ops::config_set(&key, &value),
+      ConfigSubcommand::Unset { key } => ops::config_unset(&key),
+      ConfigSubcommand::List => ops::config_list(),
+      ConfigSubcommand::Edit => ops::config_edit(),
+    },
+    RoxySubcommand::Logs(props) => ops::logs(props),
+  };
+
+  if let Err(err) = &result {
+    report::fail(err);
   }
+  process::exit(report::finish(&command));
+}
+
+/// The subcommand as typed, e.g. "autostart enable", reported with --json
+fn command_name(command: &RoxySubcommand) -> String {
+  let name = match command {
+    RoxySubcommand::Status => "status",
+    RoxySubcommand::Install(_) => "install",
+    RoxySubcommand::Uninstall => "uninstall",
+    RoxySubcommand::Setup(_) => "setup",
+    RoxySubcommand::Update(_) => "update",
+    RoxySubcommand::Start(_) => "start",
+    RoxySubcommand::Restart(_) => "restart",
+    RoxySubcommand::Stop => "stop",
+    RoxySubcommand::Run => "run",
+    RoxySubcommand::Supervise(_) => "supervise",
+    RoxySubcommand::Watchdog(_) => "watchdog",
+    RoxySubcommand::Autostart(sc) => match sc.command {
+      AutostartSubcommand::Enable => "autostart enable",
+      AutostartSubcommand::Disable => "autostart disable",
+      AutostartSubcommand::Status => "autostart status",
+    },
+    RoxySubcommand::Config(sc) => match sc.command {
+      ConfigSubcommand::Get { .. } => "config get",
+      ConfigSubcommand::Set { .. } => "config set",
+      ConfigSubcommand::Unset { .. } => "config unset",
+      ConfigSubcommand::List => "config list",
+      ConfigSubcommand::Edit => "config edit",
+    },
+    RoxySubcommand::Logs(_) => "logs",
+  };
+  name.to_string()
 }
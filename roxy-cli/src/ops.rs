@@ -1,20 +1,70 @@
 use colored::Colorize;
+use indicatif::ProgressBar;
 use std::fs::{self, File};
-use std::io::{Read, Write};
 use std::path::Path;
-use std::process::{Command, Stdio};
-use std::time::Duration;
+use std::process::Stdio;
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::console::{confirm, start_simple_progress_bar};
+use crate::args::{
+  InstallCommand, LogsCommand, ServiceManagerKind, SetupCommand, UpdateCommand, VerifyArgs,
+  WatchdogOptions, START_TIMEOUT_SECS,
+};
+use crate::build::{build_roxy, Entrypoint, PackageJson};
+use crate::config::{RoxyConfig, RoxyConfigFile};
+use crate::console::{confirm, open_editor, print_diff, start_simple_progress_bar};
+use crate::error::RoxyCliError;
+use crate::journal::{catch_interrupts, Journal, StepError, StepFailure};
+use crate::logrotate::rotate_logs;
+use crate::logs::print_logs;
+use crate::report::{field, say, set_exit_code};
+use crate::server::{
+  catch_forwarded_signals, run_foreground, server_command, stop_requested, PidFile,
+};
+use crate::service::{self, install_binary, ServiceManager};
+use crate::setup::ask_settings;
+use crate::supervisor::{mark_hang_kill, supervise as run_supervisor, SupervisorState};
 use crate::utils::{
-  create_url, download_file, generate_token, is_running, unzip_file, Autostart, InstallInfo,
+  create_url, fetch_node, fetch_roxy, generate_token, is_running, latest_release_tag, log_tail,
+  node_archive_url, node_checksums_url, release_checksums_url, remove_dir, rename,
+  roxy_archive_url, sleep_unless, source_file_name, write_dot_env, write_private, InstallInfo,
 };
-
-pub fn status() {
+use crate::verify::{checksum_from_manifest, ArchiveCheck};
+use crate::watchdog::Watchdog;
+
+/// Lines of the server output and roxy.log shown when roxy fails to start
+const STARTUP_LOG_TAIL_LINES: usize = 20;
+/// How long `stop` waits for roxy to exit after asking it over HTTP
+const STOP_REQUEST_WAIT: Duration = Duration::from_secs(5);
+/// How long `stop` waits after SIGTERM before resorting to SIGKILL
+const STOP_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+pub fn status() -> Result<(), RoxyCliError> {
+  let install_info = InstallInfo::get()?;
+  // A broken roxy.json is reported, the rest of the status does not need it
+  let (config, config_error) = if install_info.installed {
+    match RoxyConfig::read(&install_info) {
+      Ok(config) => (Some(config), None),
+      Err(err) => (None, Some(err)),
+    }
+  } else {
+    (None, None)
+  };
   let pb = start_simple_progress_bar("Loading...");
+  if install_info.installed {
+    for err in rotate_logs(&install_info) {
+      pb.println(format!("{}", err.yellow()));
+    }
+  }
 
-  let install_info = InstallInfo::get();
-  let is_running = is_running();
+  let pid_file = PidFile::read(&install_info);
+  // Without the port only the PID file can tell
+  let is_running = match config {
+    Some(_) => is_running(),
+    None => pid_file.is_some(),
+  };
+  let supervisor = SupervisorState::read(&install_info);
+  let autostart = service::detect().map(|manager| (manager.kind(), manager.is_enabled()));
 
   pb.finish_and_clear();
 
@@ -34,23 +84,118 @@ pub fn status() {
     "Stopped".red()
   };
 
-  println!(
+  say(format!(
     "{}: {} {}",
     "Installation".bold(),
     installed_text,
     path_text
+  ));
+  say(format!("{}: {}", "Version".bold(), install_info.version));
+  if let Some(config) = &config {
+    say(format!("{}: {}", "URL".bold(), config.url));
+    say(format!("{}: {}", "Port".bold(), config.port));
+  }
+  if let Some(err) = &config_error {
+    say(format!("{}: {}", "Config".bold(), err).yellow());
+  }
+  say(format!("{}: {}", "Status".bold(), status_text));
+  field("installed", install_info.installed);
+  field("path", &install_info.path);
+  field("version", &install_info.version);
+  field("url", config.as_ref().map(|config| &config.url));
+  field("port", config.as_ref().map(|config| config.port));
+  field("config_error", config_error.map(|err| err.to_string()));
+  field("running", is_running);
+
+  let uptime = pid_file.as_ref().and_then(PidFile::uptime);
+  if let Some(pid_file) = &pid_file {
+    say(format!("{}: {}", "PID".bold(), pid_file.pid));
+  }
+  if let Some(uptime) = uptime {
+    say(format!("{}: {}", "Uptime".bold(), format_duration(uptime)));
+  }
+  field("pid", pid_file.map(|pid_file| pid_file.pid));
+  field("uptime", uptime.map(|uptime| uptime.as_secs()));
+
+  if let Some((kind, enabled)) = autostart {
+    let autostart_text = if enabled {
+      "Enabled".green()
+    } else {
+      "Disabled".red()
+    };
+    say(format!(
+      "{}: {} ({})",
+      "Autostart".bold(),
+      autostart_text,
+      kind
+    ));
+  }
+  field(
+    "autostart",
+    autostart.map(|(kind, enabled)| {
+      serde_json::json!({ "enabled": enabled, "service_manager": kind.to_string() })
+    }),
+  );
+
+  if let Some(supervisor) = &supervisor {
+    let supervisor_text = if supervisor.process.is_alive() {
+      "Running".green()
+    } else {
+      "Stopped".red()
+    };
+    say(format!("{}: {}", "Supervisor".bold(), supervisor_text));
+    say(format!("{}: {}", "Restarts".bold(), supervisor.restarts));
+    if let Some(crash) = &supervisor.last_crash {
+      say(format!(
+        "{}: {} ({})",
+        "Last crash".bold(),
+        crash.reason,
+        crash
+          .time
+          .with_timezone(&chrono::Local)
+          .format("%Y-%m-%d %H:%M:%S")
+      ));
+    }
+  }
+  field(
+    "supervisor",
+    supervisor.map(|supervisor| {
+      serde_json::json!({
+        "running": supervisor.process.is_alive(),
+        "restarts": supervisor.restarts,
+        "last_crash": supervisor.last_crash,
+      })
+    }),
   );
-  println!("{}: {}", "Version".bold(), install_info.version);
-  println!("{}: {}", "Status".bold(), status_text);
+  Ok(())
+}
+
+/// e.g. 2d 3h 4m 5s, leaving out leading zero units
+fn format_duration(duration: Duration) -> String {
+  let secs = duration.as_secs();
+  let parts = [
+    (secs / 86400, "d"),
+    (secs / 3600 % 24, "h"),
+    (secs / 60 % 60, "m"),
+    (secs % 60, "s"),
+  ];
+  let first = parts
+    .iter()
+    .position(|(value, _)| *value > 0)
+    .unwrap_or(parts.len() - 1);
+  parts[first..]
+    .iter()
+    .map(|(value, unit)| format!("{}{}", value, unit))
+    .collect::<Vec<_>>()
+    .join(" ")
 }
 
-pub fn install(path: Option<String>) {
-  if InstallInfo::get().installed {
-    println!("{}", "Roxy is already installed".bold().red());
-    return;
+pub fn install(props: InstallCommand) -> Result<(), RoxyCliError> {
+  if InstallInfo::get()?.installed {
+    return Err(RoxyCliError::AlreadyInstalled);
   }
 
-  let install_info = InstallInfo::with_or_default(path);
+  let install_info = InstallInfo::with_or_default(props.path.clone())?;
   if !confirm(
     format!(
       "Are you sure you want to install roxy to: {:?}",
@@ -58,219 +203,358 @@ pub fn install(path: Option<String>) {
     )
     .bold(),
   ) {
-    return;
+    return Err(RoxyCliError::Cancelled);
   }
 
-  if Path::new(&install_info.app_path()).exists() {
-    fs::remove_dir_all(&install_info.app_path()).expect("Failed to clean up existing installation");
-  }
-
-  let pb = start_simple_progress_bar("Downloading Node.js...");
-
-  fs::create_dir_all(install_info.app_path()).expect("Failed to create app folder");
-
-  let node_url;
-  let node_path;
+  // Asked before anything is downloaded, so roxy.json is ready for the first start
+  let mut config_file = RoxyConfigFile::read(&install_info)?;
+  let settings = ask_settings(
+    &config_file,
+    &props.setup.settings,
+    !props.setup.non_interactive,
+  )?;
+  config_file.set_values(settings)?;
+
+  catch_interrupts().map_err(RoxyCliError::Other)?;
+  let pb = start_simple_progress_bar("Preparing...");
+  let mut journal = Journal::new();
+
+  if let Err(err) = install_steps(&mut journal, &props, &install_info, &config_file, &pb) {
+    pb.set_message("Rolling back...");
+    let rollback_errors = journal.rollback();
+    pb.finish_and_clear();
 
-  #[cfg(target_os = "windows")]
-  {
-    node_url = "https://nodejs.org/dist/v18.16.0/node-v18.16.0-win-x64.zip";
-    node_path = install_info.app_path().join("node.zip")
+    if rollback_errors.is_empty() {
+      say("All changes have been rolled back".yellow());
+    }
+    for err in &rollback_errors {
+      say(format!("Failed to roll back \"{}\": {}", err.step, err.reason).red());
+    }
+    field(
+      "rollback_errors",
+      rollback_errors
+        .iter()
+        .map(|err| format!("{}: {}", err.step, err.reason))
+        .collect::<Vec<_>>(),
+    );
+    return Err(install_failure(err));
   }
 
-  #[cfg(target_os = "linux")]
-  {
-    node_url = "https://nodejs.org/dist/v18.16.0/node-v18.16.0-linux-x64.tar.gz";
-    node_path = install_info.app_path().join("node.tar.gz")
-  }
-
-  download_file(node_url, &node_path);
-  unzip_file(&node_path, &install_info.app_path());
-  fs::remove_file(node_path).expect("Failed to delete Node.js zip");
-
-  // Names are different on linux and windows so we just find it on runtime
-  let entries = fs::read_dir(install_info.app_path()).expect("Failed to read app directory");
-  for entry in entries {
-    let entry = entry.unwrap();
-    if entry.file_type().unwrap().is_dir() && entry.file_name().to_string_lossy().contains("node") {
-      fs::rename(entry.path(), entry.path().with_file_name("node"))
-        .expect("Failed to rename node folder");
+  pb.finish_and_clear();
+  say("Successfully installed roxy!".green().bold());
+  let install_info = InstallInfo::get()?;
+  field("installed", true);
+  field("path", &install_info.path);
+  field("version", &install_info.version);
+
+  if service::detect().is_some() {
+    if let Err(err) = autostart_enable(None) {
+      say(format!("Failed to enable autostart: {}", err).yellow());
     }
   }
+  start(Duration::from_secs(START_TIMEOUT_SECS))
+}
 
-  pb.set_message("Downloading roxy...");
+/// The error the failed install step returned, with the step it happened in
+fn install_failure(failure: StepFailure) -> RoxyCliError {
+  failure
+    .error
+    .map_message(|message| format!("Installation failed at {}: {}", failure.step, message))
+}
 
-  let roxy_url;
-  let roxy_zip_path;
+fn install_steps(
+  journal: &mut Journal,
+  props: &InstallCommand,
+  install_info: &InstallInfo,
+  config_file: &RoxyConfigFile,
+  pb: &ProgressBar,
+) -> Result<(), StepFailure> {
+  let data_path = install_info.path.clone();
+  let app_path = install_info.app_path();
+  let previous_app_path = install_info.path.join("app.old");
+
+  let data_path_existed = data_path.exists();
+  journal.run(
+    "Create data folder",
+    || {
+      fs::create_dir_all(&data_path).map_err(RoxyCliError::io(format!(
+        "Failed to create {:?}",
+        data_path
+      )))
+    },
+    {
+      let data_path = data_path.clone();
+      let install_log_path = install_info.install_log_path();
+      // The install log is kept so the failure can still be looked into
+      move || {
+        if data_path_existed || install_log_path.exists() {
+          Ok(())
+        } else {
+          fs::remove_dir_all(&data_path).map_err(|err| err.to_string())
+        }
+      }
+    },
+  )?;
+
+  // An app folder left behind by a broken install is only deleted once we succeed
+  journal.run(
+    "Move previous app folder",
+    || {
+      if !app_path.exists() {
+        return Ok(());
+      }
+      remove_dir(&previous_app_path)?;
+      rename(&app_path, &previous_app_path)
+    },
+    {
+      let app_path = app_path.clone();
+      let previous_app_path = previous_app_path.clone();
+      move || {
+        if !previous_app_path.exists() {
+          return Ok(());
+        }
+        remove_dir(&app_path).map_err(|err| err.to_string())?;
+        fs::rename(&previous_app_path, &app_path).map_err(|err| err.to_string())
+      }
+    },
+  )?;
 
-  #[cfg(target_os = "windows")]
-  {
-    roxy_url = "https://github.com/keifufu/roxy/archive/main.zip";
-    roxy_zip_path = install_info.app_path().join("roxy.zip")
-  }
+  journal.run(
+    "Create app folder",
+    || {
+      fs::create_dir_all(&app_path)
+        .map_err(RoxyCliError::io(format!("Failed to create {:?}", app_path)))
+    },
+    {
+      let app_path = app_path.clone();
+      move || remove_dir(&app_path).map_err(|err| err.to_string())
+    },
+  )?;
 
-  #[cfg(target_os = "linux")]
-  {
-    roxy_url = "https://github.com/keifufu/roxy/archive/main.tar.gz";
-    roxy_zip_path = install_info.app_path().join("roxy.tar.gz")
-  }
+  let node_source = match &props.node_from {
+    Some(node_from) => node_from.clone(),
+    None => node_archive_url(&props.node_version),
+  };
+  let roxy_source = match (&props.from, &props.version, &props.git_ref) {
+    (Some(from), _, _) => from.clone(),
+    (None, Some(version), _) => {
+      roxy_archive_url(&format!("refs/tags/v{}", version.trim_start_matches('v')))
+    }
+    (None, None, Some(git_ref)) => roxy_archive_url(git_ref),
+    (None, None, None) => roxy_archive_url("main"),
+  };
 
-  download_file(roxy_url, &roxy_zip_path);
-  unzip_file(&roxy_zip_path, &install_info.app_path());
-  fs::remove_file(roxy_zip_path).expect("Failed to delete roxy zip");
+  pb.set_message("Fetching checksums...");
+  let (node_check, roxy_check) = journal.run(
+    "Fetch checksums",
+    || {
+      if props.skip_verify {
+        return Ok((ArchiveCheck::default(), ArchiveCheck::default()));
+      }
+      Ok((
+        node_archive_check(props, &node_source, pb)?,
+        roxy_archive_check(
+          &props.verify,
+          props
+            .version
+            .as_ref()
+            .map(|version| release_checksums_url(&format!("v{}", version.trim_start_matches('v')))),
+          &roxy_source,
+          pb,
+        )?,
+      ))
+    },
+    || Ok(()),
+  )?;
+  if !props.skip_verify && roxy_check.is_empty() {
+    pb.println(format!(
+      "{}",
+      "Warning: the roxy archive will not be verified, pass --checksums or --public-key to verify it"
+        .yellow()
+    ));
+  }
 
-  // roxy-main/roxy -> roxy
-  fs::rename(
-    install_info.app_path().join("roxy-main").join("roxy"),
-    install_info.roxy_path(),
-  )
-  .expect("Failed to rename roxy folder");
+  pb.set_message("Downloading Node.js...");
+  journal.run(
+    "Download Node.js",
+    || fetch_node(&node_source, &node_check, install_info, pb),
+    {
+      let node_path = install_info.node_path();
+      move || remove_dir(&node_path).map_err(|err| err.to_string())
+    },
+  )?;
 
-  fs::remove_dir_all(install_info.app_path().join("roxy-main"))
-    .expect("Failed to delete roxy folder");
+  pb.set_message("Downloading roxy...");
+  journal.run(
+    "Download roxy",
+    || {
+      fetch_roxy(
+        &roxy_source,
+        &roxy_check,
+        install_info,
+        &install_info.roxy_path(),
+        pb,
+      )
+    },
+    {
+      let roxy_path = install_info.roxy_path();
+      move || remove_dir(&roxy_path).map_err(|err| err.to_string())
+    },
+  )?;
 
   pb.set_message("Building...");
-
   let termination_token = generate_token(24);
-  let dot_env_contents = format!(
-    "DATA_PATH={}\nTERMINATION_TOKEN={}",
-    install_info.path.to_string_lossy().to_string(),
-    termination_token
-  );
-  match File::create(install_info.roxy_path().join(".env")) {
-    Ok(mut file) => {
-      if let Err(err) = file.write_all(dot_env_contents.as_bytes()) {
-        panic!("Failed to write file: {}", err);
+  journal.run(
+    "Write .env",
+    || {
+      write_dot_env(
+        &install_info.roxy_path(),
+        &install_info.path,
+        &termination_token,
+      )
+    },
+    || Ok(()),
+  )?;
+
+  journal.run(
+    "Build roxy",
+    || build_roxy(install_info, &install_info.roxy_path(), props.verbose, pb),
+    || Ok(()),
+  )?;
+
+  journal.run(
+    "Verify entrypoint",
+    || {
+      Entrypoint::resolve(&install_info.roxy_path())
+        .map(|_| ())
+        .map_err(|err| with_install_log(err, install_info, props.verbose))
+    },
+    || Ok(()),
+  )?;
+
+  // An earlier install may have left a roxy.json behind, which is put back as it was
+  let previous_config = fs::read_to_string(&config_file.path).ok();
+  journal.run("Write roxy.json", || config_file.write(), {
+    let path = config_file.path.clone();
+    move || {
+      match previous_config {
+        Some(contents) => fs::write(&path, contents),
+        None => fs::remove_file(&path),
       }
+      .map_err(|err| err.to_string())
     }
-    Err(err) => {
-      panic!("Failed to create file: {}", err);
-    }
-  }
+  })?;
+
+  journal.run(
+    "Write roxy-cli.json",
+    || {
+      let package = PackageJson::read(&install_info.roxy_path())?;
+      InstallInfo::write(
+        install_info.path.clone(),
+        &package.version,
+        termination_token.clone(),
+      )
+    },
+    || InstallInfo::reset().map_err(|err| err.to_string()),
+  )?;
 
-  #[cfg(target_os = "windows")]
-  {
-    Command::new(install_info.node_path().join("npm.cmd"))
-      .args(["install"])
-      .current_dir(install_info.roxy_path())
-      .output()
-      .expect("Failed to install dependencies with npm");
+  journal.run(
+    "Remove previous app folder",
+    || remove_dir(&previous_app_path),
+    || Ok(()),
+  )
+}
 
-    Command::new(install_info.node_path().join("npm.cmd"))
-      .args(["run", "build"])
-      .current_dir(install_info.roxy_path())
-      .output()
-      .expect("Failed to build roxy with npm");
+/// A build that did not produce a runnable roxy, with the install log npm's
+/// output went to
+fn with_install_log(err: RoxyCliError, install_info: &InstallInfo, verbose: bool) -> RoxyCliError {
+  match err {
+    RoxyCliError::ChildProcess { message, log: None } => RoxyCliError::ChildProcess {
+      message,
+      log: (!verbose).then(|| install_info.install_log_path()),
+    },
+    err => err,
   }
+}
 
-  #[cfg(target_os = "linux")]
-  {
-    use std::os::unix::fs::PermissionsExt;
-    let build_sh_contents = r#"
-      #!/bin/bash
-      export PATH="../node/bin:$PATH"
-      export NODE_PATH="../node/lib/node_modules"
-      npm run build
-    "#;
-
-    let build_sh_path = install_info.roxy_path().join("build.sh");
-
-    match File::create(&build_sh_path) {
-      Ok(mut file) => {
-        if let Err(err) = file.write_all(build_sh_contents.as_bytes()) {
-          panic!("Failed to write file: {}", err);
-        }
-      }
-      Err(err) => {
-        panic!("Failed to create file: {}", err);
-      }
-    }
-
-    let mut permissions = fs::metadata(&build_sh_path)
-      .expect("Failed to retrieve permissions from build.sh")
-      .permissions();
-    let mode = permissions.mode() | 0o755;
-    permissions.set_mode(mode);
-    fs::set_permissions(&build_sh_path, permissions)
-      .expect("Failed to set file permissions for build.sh");
-
-    Command::new(install_info.node_path().join("bin").join("node"))
-      .args([
-        install_info
-          .node_path()
-          .join("bin")
-          .join("npm")
-          .to_str()
-          .unwrap(),
-        "install",
-      ])
-      .current_dir(install_info.roxy_path())
-      .output()
-      .expect("Failed to install dependencies with npm");
-
-    Command::new("sh")
-      .arg(&build_sh_path)
-      .current_dir(install_info.roxy_path())
-      .output()
-      .expect("Failed to build roxy with build.sh");
-  }
-
-  let mut file =
-    File::open(install_info.roxy_path().join("package.json")).expect("Failed to open package.json");
-  let mut contents = String::new();
-  file
-    .read_to_string(&mut contents)
-    .expect("Failed to read package.json");
-
-  let json: serde_json::Value =
-    serde_json::from_str(&contents).expect("Failed to parse package.json");
-
-  InstallInfo::write(
-    install_info.path,
-    json["version"].as_str().unwrap(),
-    termination_token,
-  );
+fn node_archive_check(
+  props: &InstallCommand,
+  source: &str,
+  pb: &ProgressBar,
+) -> Result<ArchiveCheck, RoxyCliError> {
+  let sha256 = match (&props.node_sha256, &props.node_from) {
+    (Some(sha256), _) => Some(sha256.clone()),
+    (None, None) => Some(checksum_from_manifest(
+      &node_checksums_url(&props.node_version),
+      source_file_name(source),
+      pb,
+    )?),
+    (None, Some(_)) => None,
+  };
 
-  pb.finish_and_clear();
-  println!("{}", "Successfully installed roxy!".green().bold());
+  Ok(ArchiveCheck {
+    sha256,
+    signature: None,
+  })
+}
 
-  autostart_enable();
-  start();
+/// `release_manifest` is used unless a checksum or manifest was passed
+fn roxy_archive_check(
+  props: &VerifyArgs,
+  release_manifest: Option<String>,
+  source: &str,
+  pb: &ProgressBar,
+) -> Result<ArchiveCheck, RoxyCliError> {
+  let manifest = props.checksums.clone().or(release_manifest);
+  let sha256 = match (&props.sha256, manifest) {
+    (Some(sha256), _) => Some(sha256.clone()),
+    (None, Some(manifest)) => Some(checksum_from_manifest(
+      &manifest,
+      source_file_name(source),
+      pb,
+    )?),
+    (None, None) => None,
+  };
+  let signature = props.public_key.as_ref().map(|public_key| {
+    let signature = props
+      .signature
+      .clone()
+      .unwrap_or_else(|| format!("{}.minisig", source));
+    (signature, public_key.clone())
+  });
+
+  Ok(ArchiveCheck { sha256, signature })
 }
 
-pub fn uninstall() {
-  let install_info = InstallInfo::get();
+pub fn uninstall() -> Result<(), RoxyCliError> {
+  let install_info = InstallInfo::get()?;
 
   if !install_info.installed {
-    println!("{}", "Roxy is not installed".bold().red());
-    return;
+    return Err(RoxyCliError::NotInstalled);
   }
 
   if !confirm("Are you sure you want to uninstall roxy?".normal()) {
-    return;
+    return Err(RoxyCliError::Cancelled);
   }
 
   let delete_all = confirm("Do you want to delete the database and uploaded files too? (There will be no more confirmations)".normal());
 
-  let pb = start_simple_progress_bar("Loading...");
-
   if is_running() {
-    stop();
+    stop()?;
   }
 
-  pb.set_message("Deleting roxy...");
-
-  let bin_path = install_info.path.join("app");
-  fs::remove_dir_all(bin_path).expect("Failed to delete roxy");
-
-  if delete_all {
+  let pb = start_simple_progress_bar("Deleting roxy...");
+  let bin_path = install_info.app_path();
+  let mut result = remove_dir(&bin_path);
+  if delete_all && result.is_ok() {
     pb.set_message("Deleting database and uploaded files...");
-    fs::remove_dir_all(&install_info.path).expect("Failed to delete database and uploaded files");
+    result = remove_dir(&install_info.path);
   }
-
   pb.finish_and_clear();
-  InstallInfo::reset();
-  println!(
+  result?;
+
+  InstallInfo::reset()?;
+  say(format!(
     "{}\n{}",
     "Successfully uninstalled roxy!".bold().green(),
     if delete_all {
@@ -278,128 +562,841 @@ pub fn uninstall() {
     } else {
       "(Database and uploaded files were kept)".bold().green()
     },
-  );
+  ));
   if !delete_all {
-    println!("Data path: {:?}", install_info.path);
+    say(format!("Data path: {:?}", install_info.path));
+  }
+  field("installed", false);
+  field("path", &install_info.path);
+  field("data_deleted", delete_all);
+  Ok(())
+}
+
+pub fn update(props: UpdateCommand) -> Result<(), RoxyCliError> {
+  let install_info = InstallInfo::get()?;
+  if !install_info.installed {
+    return Err(RoxyCliError::NotInstalled);
+  }
+
+  let pb = start_simple_progress_bar("Checking for updates...");
+  let tag = match latest_release_tag() {
+    Some(tag) => tag,
+    None => {
+      pb.finish_and_clear();
+      return Err(RoxyCliError::Network(String::from(
+        "Failed to check for updates",
+      )));
+    }
+  };
+  pb.finish_and_clear();
+
+  let version = tag.trim_start_matches('v');
+  field("previous_version", &install_info.version);
+  field("version", &install_info.version);
+  field("updated", false);
+  if version == install_info.version {
+    say("Roxy is already up to date".green().bold());
+    return Ok(());
+  }
+
+  if !confirm(format!("Update roxy from {} to {}?", install_info.version, version).normal()) {
+    return Err(RoxyCliError::Cancelled);
+  }
+
+  // The new version is built next to the current one, so a failed download
+  // or build never touches the running installation
+  let staging_path = install_info.app_path().join("roxy-update");
+  let backup_path = install_info.app_path().join("roxy-backup");
+  for path in [&staging_path, &backup_path] {
+    remove_dir(path)?;
+  }
+
+  let pb = start_simple_progress_bar(&format!("Downloading roxy {}...", version));
+  let roxy_source = roxy_archive_url(&format!("refs/tags/{}", tag));
+  let check = if props.skip_verify {
+    pb.println(format!(
+      "{}",
+      "Warning: the roxy archive will not be verified".yellow()
+    ));
+    ArchiveCheck::default()
+  } else {
+    roxy_archive_check(
+      &props.verify,
+      Some(release_checksums_url(&tag)),
+      &roxy_source,
+      &pb,
+    )
+    .map_err(|err| {
+      pb.finish_and_clear();
+      err.map_message(|message| {
+        format!(
+          "{}\nPass --sha256, --checksums or --public-key to verify roxy {} another way, or --skip-verify\nUpdate aborted, nothing was changed",
+          message, version
+        )
+      })
+    })?
+  };
+  if let Err(err) = fetch_roxy(&roxy_source, &check, &install_info, &staging_path, &pb) {
+    pb.finish_and_clear();
+    return Err(
+      err.map_message(|message| format!("{}\nUpdate aborted, nothing was changed", message)),
+    );
+  }
+  if let Err(err) = fs::copy(
+    install_info.roxy_path().join(".env"),
+    staging_path.join(".env"),
+  ) {
+    pb.finish_and_clear();
+    return Err(RoxyCliError::io("Failed to copy .env")(err));
+  }
+
+  pb.set_message("Building...");
+  let built = build_roxy(&install_info, &staging_path, props.verbose, &pb).and_then(|_| {
+    Entrypoint::resolve(&staging_path)
+      .map(|_| ())
+      .map_err(|err| with_install_log(err, &install_info, props.verbose))
+  });
+  if let Err(err) = built {
+    pb.finish_and_clear();
+    remove_dir(&staging_path)?;
+    return Err(err.map_message(|message| {
+      format!(
+        "{}\nFailed to build the new version, nothing was changed",
+        message
+      )
+    }));
+  }
+  pb.finish_and_clear();
+  let new_version = PackageJson::read(&staging_path)?.version;
+
+  let was_running = is_running();
+  if was_running {
+    if let Err(err) = stop() {
+      remove_dir(&staging_path)?;
+      return Err(err);
+    }
+  }
+
+  let swapped = rename(&install_info.roxy_path(), &backup_path)
+    .and_then(|_| rename(&staging_path, &install_info.roxy_path()))
+    .and_then(|_| {
+      InstallInfo::write(
+        install_info.path.clone(),
+        &new_version,
+        install_info.termination_token.clone(),
+      )
+    });
+  let failure = match swapped {
+    Err(err) => err,
+    Ok(()) => match start(Duration::from_secs(START_TIMEOUT_SECS)) {
+      Err(err) => {
+        err.map_message(|message| format!("Roxy {} did not come back up: {}", new_version, message))
+      }
+      Ok(()) => {
+        remove_dir(&backup_path)?;
+        if !was_running {
+          stop()?;
+        }
+        say(
+          format!("Successfully updated roxy to {}!", new_version)
+            .green()
+            .bold(),
+        );
+        field("version", &new_version);
+        field("updated", true);
+        return Ok(());
+      }
+    },
+  };
+
+  say(format!("{}, rolling back...", failure).red().bold());
+  let rollback_errors = roll_back_update(&install_info, &staging_path, &backup_path);
+  for err in &rollback_errors {
+    say(format!("Failed to roll back \"{}\": {}", err.step, err.reason).red());
+  }
+  field(
+    "rollback_errors",
+    rollback_errors
+      .iter()
+      .map(|err| format!("{}: {}", err.step, err.reason))
+      .collect::<Vec<_>>(),
+  );
+  // The old version is only back in place if it could be moved back
+  let restored = !rollback_errors
+    .iter()
+    .any(|err| err.step == "Restore previous version");
+  field("rolled_back", restored);
+  if !restored {
+    return Err(RoxyCliError::Start(format!(
+      "Updating roxy to {} failed and rolling back failed, {:?} has to be fixed by hand",
+      new_version,
+      install_info.app_path()
+    )));
+  }
+
+  if was_running {
+    start(Duration::from_secs(START_TIMEOUT_SECS))?;
   }
+  Err(
+    failure.map_message(|message| format!("{}\nRolled back to {}", message, install_info.version)),
+  )
 }
 
-pub fn update() {
-  println!("Update: not yet implemented");
+/// Puts the previous version back after swapping in the new one or starting
+/// it failed. Every step is tried even if one before it failed, like
+/// `Journal::rollback`.
+fn roll_back_update(
+  install_info: &InstallInfo,
+  staging_path: &Path,
+  backup_path: &Path,
+) -> Vec<StepError> {
+  let mut errors = Vec::new();
+  let mut record = |step: &str, result: Result<(), String>| {
+    if let Err(reason) = result {
+      errors.push(StepError {
+        step: step.to_string(),
+        reason,
+      });
+    }
+  };
+
+  // A new version that hung is still alive and would keep the old one off its port
+  if PidFile::read(install_info).is_some() || is_running() {
+    record("Stop new version", stop().map_err(|err| err.to_string()));
+  }
+  // Without a backup the previous version was never moved, so it is still in place
+  let mut restored_ok = false;
+  if backup_path.exists() {
+    // The new version may not have made it into place either
+    if install_info.roxy_path().exists() {
+      record(
+        "Move new version aside",
+        rename(&install_info.roxy_path(), staging_path).map_err(|err| err.to_string()),
+      );
+    }
+    let restored = rename(backup_path, &install_info.roxy_path()).map_err(|err| err.to_string());
+    restored_ok = restored.is_ok();
+    record("Restore previous version", restored);
+  }
+  record(
+    "Remove new version",
+    remove_dir(staging_path).map_err(|err| err.to_string()),
+  );
+  // Otherwise the new version is still in place, and so is its version number
+  if restored_ok {
+    record(
+      "Restore version in config",
+      InstallInfo::write(
+        install_info.path.clone(),
+        &install_info.version,
+        install_info.termination_token.clone(),
+      )
+      .map_err(|err| err.to_string()),
+    );
+  }
+  errors
 }
 
-pub fn start() {
-  let install_info = InstallInfo::get();
+/// Starts roxy in the background and waits until `/alive` responds.
+/// Fails if roxy exited or did not respond within `timeout`.
+// roxy is detached on purpose, the child keeps running after roxy-cli exits
+#[allow(clippy::zombie_processes)]
+pub fn start(timeout: Duration) -> Result<(), RoxyCliError> {
+  let install_info = InstallInfo::get()?;
   if !install_info.installed {
-    println!("{}", "Roxy is not installed".red().bold());
-    return;
+    return Err(RoxyCliError::NotInstalled);
   }
+  // Roxy could never be seen as running with a broken roxy.json
+  RoxyConfig::read(&install_info)?;
 
   if is_running() {
-    println!("{}", "Roxy is already running".red().bold());
-    return;
+    say("Roxy is already running".red().bold());
+    field("running", true);
+    field(
+      "pid",
+      PidFile::read(&install_info).map(|pid_file| pid_file.pid),
+    );
+    return Ok(());
   }
 
-  #[cfg(target_os = "windows")]
-  {
-    Command::new(install_info.node_path().join("node.exe"))
-      .args([install_info.roxy_path().join("dist").join("index.js")])
-      .stdout(Stdio::null())
-      .stdin(Stdio::null())
-      .spawn()
-      .expect("Failed to start roxy");
-  }
+  let start_error = |err| RoxyCliError::Start(format!("Failed to start roxy: {}", err));
+  let mut command = server_command(&install_info).map_err(start_error)?;
 
+  // Its own process group, so `stop` can signal npm and node together
   #[cfg(target_os = "linux")]
-  {
-    Command::new(install_info.node_path().join("bin").join("node"))
-      .args([install_info.roxy_path().join("dist").join("index.js")])
-      .stdout(Stdio::null())
-      .stdin(Stdio::null())
-      .spawn()
-      .expect("Failed to start roxy");
+  std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+  for err in rotate_logs(&install_info) {
+    say(err.yellow());
+  }
+  let out_log_path = install_info.server_out_log_path();
+  let err_log_path = install_info.server_err_log_path();
+  let mut child = command
+    .stdout(open_log(&out_log_path)?)
+    .stderr(open_log(&err_log_path)?)
+    .stdin(Stdio::null())
+    .spawn()
+    .map_err(|err| start_error(err.to_string()))?;
+
+  if let Err(err) = PidFile::write(&install_info, child.id()) {
+    say(err.yellow());
   }
 
-  println!("{}", "Roxy has been started!".green().bold());
+  let pb = start_simple_progress_bar("Starting roxy...");
+  let started = Instant::now();
+  let error = loop {
+    if is_running() {
+      pb.finish_and_clear();
+      say("Roxy has been started!".green().bold());
+      field("running", true);
+      field("pid", child.id());
+      return Ok(());
+    }
+    if let Ok(Some(status)) = child.try_wait() {
+      PidFile::remove(&install_info);
+      break format!("Roxy exited during startup with {}", status);
+    }
+    if started.elapsed() >= timeout {
+      break format!(
+        "Roxy did not respond within {}s, it may still be starting",
+        timeout.as_secs()
+      );
+    }
+    thread::sleep(Duration::from_millis(250));
+  };
+  pb.finish_and_clear();
+
+  field("running", false);
+  for path in [out_log_path, err_log_path, install_info.roxy_log_path()] {
+    if path.exists() {
+      say(format!("{}\n", log_tail(&path, STARTUP_LOG_TAIL_LINES)));
+    }
+  }
+  Err(RoxyCliError::Start(error))
 }
 
-pub fn restart() {
+/// Runs roxy in the foreground, for systemd, Docker and the like.
+/// roxy-cli exits with roxy's exit code.
+pub fn run() -> Result<(), RoxyCliError> {
+  let install_info = InstallInfo::get()?;
+  if !install_info.installed {
+    return Err(RoxyCliError::NotInstalled);
+  }
+
   if is_running() {
-    stop();
+    return Err(already_running());
   }
-  start();
+
+  let code = run_foreground(&install_info).map_err(RoxyCliError::Start)?;
+  field("exit_code", code);
+  set_exit_code(code);
+  Ok(())
 }
 
-pub fn stop() {
+/// Runs roxy in the foreground and restarts it when it crashes.
+/// roxy-cli exits with roxy's last exit code.
+pub fn supervise(max_restarts: u32, watchdog: Option<WatchdogOptions>) -> Result<(), RoxyCliError> {
+  let install_info = InstallInfo::get()?;
+  if !install_info.installed {
+    return Err(RoxyCliError::NotInstalled);
+  }
+
+  if is_running() {
+    return Err(already_running());
+  }
+
+  let code = run_supervisor(&install_info, max_restarts, watchdog).map_err(RoxyCliError::Start)?;
+  field("exit_code", code);
+  set_exit_code(code);
+  Ok(())
+}
+
+fn already_running() -> RoxyCliError {
+  RoxyCliError::Start(String::from("Roxy is already running"))
+}
+
+/// Watches a running roxy and restarts it when it stops responding.
+/// Crashes are left to `supervise`.
+pub fn watchdog(options: WatchdogOptions) -> Result<(), RoxyCliError> {
+  let install_info = InstallInfo::get()?;
+  if !install_info.installed {
+    return Err(RoxyCliError::NotInstalled);
+  }
+
+  catch_forwarded_signals();
+  let mut watchdog = Watchdog::new(options);
+  let mut restarts = 0;
+  say("Watching roxy...".bold());
+
+  while sleep_unless(watchdog.interval(), stop_requested) {
+    for err in rotate_logs(&install_info) {
+      say(err.yellow());
+    }
+
+    let pid_file = match PidFile::read(&install_info) {
+      Some(pid_file) => pid_file,
+      None => continue,
+    };
+    let reason = match watchdog.probe(pid_file.pid) {
+      Some(reason) => reason,
+      None => continue,
+    };
+
+    say(format!("Roxy is hung: {}", reason).red().bold());
+    match watchdog.capture_diagnostics(&install_info, &reason) {
+      Ok(path) => say(format!("Diagnostics saved to {:?}", path)),
+      Err(err) => say(err.red()),
+    }
+    restarts += 1;
+
+    // A supervisor would take the SIGTERM for `roxy-cli stop` and exit, so it
+    // is told the server hung and restarts it itself
+    let supervised = SupervisorState::read(&install_info)
+      .is_some_and(|supervisor| supervisor.process.is_alive())
+      && match mark_hang_kill(&install_info, pid_file.pid, &reason) {
+        Ok(()) => true,
+        Err(err) => {
+          say(err.red());
+          false
+        }
+      };
+    if !pid_file.terminate(STOP_GRACE_PERIOD) {
+      say("Failed to stop roxy".red().bold());
+      continue;
+    }
+
+    // The supervisor removes the PID file itself, and may have written a new one by now
+    if !supervised {
+      PidFile::remove(&install_info);
+      if let Err(err) = start(Duration::from_secs(START_TIMEOUT_SECS)) {
+        say(err.to_string().red().bold());
+      }
+    }
+  }
+
+  field("restarts", restarts);
+  Ok(())
+}
+
+/// Logs are appended to, so rotating them doesn't need a restart
+fn open_log(path: &Path) -> Result<File, RoxyCliError> {
+  fs::OpenOptions::new()
+    .create(true)
+    .append(true)
+    .open(path)
+    .map_err(RoxyCliError::io(format!("Failed to open {:?}", path)))
+}
+
+pub fn restart(timeout: Duration) -> Result<(), RoxyCliError> {
+  if is_running() {
+    stop()?;
+  }
+  start(timeout)
+}
+
+/// Asks roxy to exit over HTTP, then falls back to signals if it was started
+/// with a PID file and does not go away on its own.
+pub fn stop() -> Result<(), RoxyCliError> {
+  let install_info = InstallInfo::get()?;
+  // Without roxy.json there is no port to ask roxy to stop on, but the PID
+  // file is still enough to stop it
+  let config_error = if install_info.installed {
+    RoxyConfig::read(&install_info).err()
+  } else {
+    None
+  };
+  if let Some(err) = &config_error {
+    say(format!("{}, stopping roxy by its PID instead", err).yellow());
+  }
   let pb = start_simple_progress_bar("Loading...");
 
-  if !is_running() {
+  let pid_file = PidFile::read(&install_info);
+  let is_running = config_error.is_none() && is_running();
+
+  if !is_running && pid_file.is_none() {
     pb.finish_and_clear();
-    println!("{}", "Roxy is not running".bold().red());
-    return;
+    // A broken roxy.json is why we can not tell whether roxy is running
+    return Err(config_error.unwrap_or(RoxyCliError::NotRunning));
+  }
+
+  let requested = is_running && request_termination(&install_info);
+
+  let stopped = match pid_file {
+    Some(pid_file) => {
+      let exited = (requested && pid_file.wait_for_exit(STOP_REQUEST_WAIT)) || {
+        pb.set_message(format!("Sending SIGTERM to {}...", pid_file.pid));
+        pid_file.terminate(STOP_GRACE_PERIOD)
+      };
+      if exited {
+        PidFile::remove(&install_info);
+      }
+      exited
+    }
+    // Started by something other than roxy-cli, HTTP is all we have
+    None => requested && wait_until_stopped(STOP_REQUEST_WAIT),
+  };
+
+  pb.finish_and_clear();
+  field("running", !stopped);
+  if !stopped {
+    return Err(RoxyCliError::Stop(String::from("Failed to stop roxy")));
   }
+  say("Successfully stopped roxy".bold().green());
+  Ok(())
+}
 
+fn request_termination(install_info: &InstallInfo) -> bool {
+  let url = match create_url("/terminate") {
+    Ok(url) => url,
+    Err(_) => return false,
+  };
   let client = reqwest::blocking::Client::new();
-  let response = client
-    .post(create_url("/terminate"))
+  client
+    .post(url)
     .body(
       serde_json::json!({
-        "termination_token": InstallInfo::get().termination_token
+        "termination_token": install_info.termination_token
       })
       .to_string(),
     )
     .timeout(Duration::from_secs(1))
-    .send();
+    .send()
+    .is_ok_and(|response| response.status().is_success())
+}
 
-  pb.finish_and_clear();
-  match response {
-    Ok(_) => println!("{}", "Successfully stopped roxy".bold().green()),
-    Err(_) => println!("{}", "Failed to stop roxy".bold().red()),
+/// Like `PidFile::wait_for_exit`, for a roxy we only know by its port
+fn wait_until_stopped(timeout: Duration) -> bool {
+  let started = Instant::now();
+  while started.elapsed() < timeout {
+    if !is_running() {
+      return true;
+    }
+    thread::sleep(Duration::from_millis(100));
   }
+  !is_running()
 }
 
-pub fn autostart_enable() {
-  if Autostart::is_enabled() {
-    println!("{}", "Autostart already enabled".red().bold());
-    return;
+/// The service manager to use, or why there is none
+fn service_manager(
+  kind: Option<ServiceManagerKind>,
+) -> Result<Box<dyn ServiceManager>, RoxyCliError> {
+  let manager = match kind {
+    Some(kind) => service::backend(kind),
+    None => service::detect().ok_or_else(|| {
+      RoxyCliError::ServiceManager(String::from("No supported service manager found"))
+    })?,
+  };
+
+  if !manager.is_available() {
+    return Err(RoxyCliError::ServiceManager(format!(
+      "{} is not available on this system",
+      manager.kind()
+    )));
+  }
+  Ok(manager)
+}
+
+pub fn autostart_enable(kind: Option<ServiceManagerKind>) -> Result<(), RoxyCliError> {
+  let manager = service_manager(kind)?;
+  if manager.is_enabled() {
+    say("Autostart already enabled".red().bold());
+    autostart_fields(manager.as_ref(), true);
+    return Ok(());
+  }
+  if !InstallInfo::get()?.installed {
+    return Err(RoxyCliError::NotInstalled);
+  }
+
+  install_binary()
+    .and_then(|roxy_cli| manager.install(&roxy_cli))
+    .map_err(|err| RoxyCliError::ServiceManager(format!("Failed to enable autostart: {}", err)))?;
+  say(format!(
+    "{} {} {}",
+    "Enabled autostart with".green().bold(),
+    manager.kind().to_string().green().bold(),
+    manager.location()
+  ));
+  autostart_fields(manager.as_ref(), true);
+  if let Some(hint) = manager.hint() {
+    say(&hint);
+    field("hint", hint);
   }
-  Autostart::enable();
-  println!("{}", "Enabled autostart".green().bold());
+  Ok(())
 }
 
-pub fn autostart_disable() {
-  if !Autostart::is_enabled() {
-    println!("{}", "Autostart not enabled".red().bold());
-    return;
+pub fn autostart_disable(kind: Option<ServiceManagerKind>) -> Result<(), RoxyCliError> {
+  let manager = service_manager(kind)?;
+  if !manager.is_enabled() {
+    say("Autostart not enabled".red().bold());
+    autostart_fields(manager.as_ref(), false);
+    return Ok(());
   }
-  Autostart::disable();
-  println!("{}", "Disabled autostart".green().bold());
+
+  manager
+    .uninstall()
+    .map_err(|err| RoxyCliError::ServiceManager(format!("Failed to disable autostart: {}", err)))?;
+  say("Disabled autostart".green().bold());
+  autostart_fields(manager.as_ref(), false);
+  Ok(())
 }
 
-pub fn autostart_status() {
-  if Autostart::is_enabled() {
-    println!("{}: {}", "Status".bold(), "Enabled".green().bold());
+pub fn autostart_status(kind: Option<ServiceManagerKind>) -> Result<(), RoxyCliError> {
+  let manager = service_manager(kind)?;
+
+  let enabled = manager.is_enabled();
+  if enabled {
+    say(format!("{}: {}", "Status".bold(), "Enabled".green().bold()));
   } else {
-    println!("{}: {}", "Status".bold(), "Disabled".red().bold());
+    say(format!("{}: {}", "Status".bold(), "Disabled".red().bold()));
   }
+  say(format!(
+    "{}: {} {}",
+    "Service manager".bold(),
+    manager.kind(),
+    manager.location()
+  ));
+  autostart_fields(manager.as_ref(), enabled);
+  if let Some(status) = manager.status().filter(|_| enabled) {
+    say(format!("{}: {}", "Service".bold(), status));
+    field("service_status", status);
+  }
+  Ok(())
 }
 
-pub fn config() {
-  let install_info = InstallInfo::get();
+fn autostart_fields(manager: &dyn ServiceManager, enabled: bool) {
+  field(
+    "autostart",
+    serde_json::json!({
+      "enabled": enabled,
+      "service_manager": manager.kind().to_string(),
+      "location": manager.location(),
+    }),
+  );
+}
+
+/// roxy.json of the current installation
+fn config_file() -> Result<RoxyConfigFile, RoxyCliError> {
+  let install_info = InstallInfo::get()?;
   if !install_info.installed {
-    println!("{}", "Roxy is not installed".red().bold());
-    return;
+    return Err(RoxyCliError::NotInstalled);
   }
+  RoxyConfigFile::read(&install_info)
+}
 
-  open::that_detached(install_info.path.join("roxy.json")).expect("Failed to open roxy.json");
+/// Strings without quotes, everything else as JSON
+fn format_value(value: &serde_json::Value) -> String {
+  match value {
+    serde_json::Value::String(value) => value.clone(),
+    value => value.to_string(),
+  }
 }
 
-pub fn logs() {
-  let install_info = InstallInfo::get();
+pub fn config_get(key: &str) -> Result<(), RoxyCliError> {
+  let file = config_file()?;
+  let value = file.get(key)?;
+  say(format_value(value));
+  field("key", key);
+  field("value", value);
+  Ok(())
+}
+
+pub fn config_set(key: &str, value: &str) -> Result<(), RoxyCliError> {
+  let mut file = config_file()?;
+  let changed = file.set(key, value)?;
+  apply_config_change(&file, key, changed)
+}
+
+pub fn config_unset(key: &str) -> Result<(), RoxyCliError> {
+  let mut file = config_file()?;
+  let changed = file.unset(key)?;
+  apply_config_change(&file, key, changed)
+}
+
+fn apply_config_change(
+  file: &RoxyConfigFile,
+  key: &str,
+  changed: bool,
+) -> Result<(), RoxyCliError> {
+  let value = file.get(key)?;
+  field("key", key);
+  field("value", value);
+  field("changed", changed);
+  if !changed {
+    say(format!("{} is already {}", key, format_value(value)));
+    return Ok(());
+  }
+
+  write_config(&[key.to_string()], || {
+    file.write()?;
+    say(
+      format!("Set {} to {}", key, format_value(value))
+        .green()
+        .bold(),
+    );
+    Ok(())
+  })
+}
+
+/// Writes roxy.json and offers to restart roxy, which only reads it on startup
+fn write_config(
+  changed_keys: &[String],
+  write: impl FnOnce() -> Result<(), RoxyCliError>,
+) -> Result<(), RoxyCliError> {
+  // `_` is only a comment
+  let restart = changed_keys.iter().any(|key| key != "_")
+    && is_running()
+    && confirm("Restart roxy to apply the change?".normal());
+  field("restarted", false);
+
+  // Stopped before writing, as roxy might not be found under a new port
+  if restart {
+    stop()?;
+  }
+  write()?;
+  if restart {
+    start(Duration::from_secs(START_TIMEOUT_SECS))?;
+    field("restarted", true);
+  }
+  Ok(())
+}
+
+pub fn config_list() -> Result<(), RoxyCliError> {
+  let file = config_file()?;
+  for (key, value) in file.values() {
+    say(format!("{}: {}", key.bold(), format_value(value)));
+  }
+  field("path", &file.path);
+  field("config", file.values());
+
+  if let Err(err) = file.config() {
+    say(err.to_string().yellow());
+    field("invalid", err.to_string());
+  }
+  Ok(())
+}
+
+/// Starts the lines `config_edit` puts at the top of the copy to list problems
+const EDIT_COMMENT: &str = "// roxy-cli:";
+
+/// Edits a copy of roxy.json in $VISUAL or $EDITOR, and only replaces
+/// roxy.json once the copy is valid and the diff has been confirmed.
+pub fn config_edit() -> Result<(), RoxyCliError> {
+  let file = config_file()?;
+  field("path", &file.path);
+
+  // Next to roxy.json instead of the shared temp folder, see `write_private`
+  let temp_path = file.path.with_extension("edit.json");
+  let result = edit_until_valid(&file, &temp_path);
+  fs::remove_file(&temp_path).ok();
+  let (contents, edited) = match result? {
+    Some(edited) => edited,
+    None => {
+      say("No changes");
+      field("changed", Vec::<String>::new());
+      return Ok(());
+    }
+  };
+
+  print_diff(file.contents(), &contents);
+  if !confirm("Apply these changes?".normal()) {
+    return Err(RoxyCliError::Cancelled);
+  }
+
+  let changed_keys = file.changed_keys(&edited);
+  field("changed", &changed_keys);
+  write_config(&changed_keys, || {
+    file.replace_contents(&contents)?;
+    say(format!("Saved {:?}", file.path).green().bold());
+    Ok(())
+  })
+}
+
+/// Opens the editor until the copy is valid, showing what is wrong at the top.
+/// Returns None if nothing was changed.
+fn edit_until_valid(
+  file: &RoxyConfigFile,
+  temp_path: &Path,
+) -> Result<Option<(String, RoxyConfigFile)>, RoxyCliError> {
+  let mut contents = file.contents().to_string();
+  let mut problems = None;
+
+  loop {
+    let header: String = match &problems {
+      Some(problems) => format!(
+        "{}\n\nFix the problems above and save, or delete everything to cancel",
+        problems
+      )
+      .lines()
+      .map(|line| format!("{} {}\n", EDIT_COMMENT, line))
+      .collect(),
+      None => String::new(),
+    };
+    write_private(temp_path, &(header + &contents))
+      .map_err(RoxyCliError::io(format!("Failed to write {:?}", temp_path)))?;
+
+    open_editor(temp_path)?;
+
+    contents = fs::read_to_string(temp_path)
+      .map_err(RoxyCliError::io(format!("Failed to read {:?}", temp_path)))?;
+    while contents.starts_with(EDIT_COMMENT) {
+      contents = contents
+        .split_once('\n')
+        .map(|(_, rest)| rest.to_string())
+        .unwrap_or_default();
+    }
+    if contents.trim().is_empty() {
+      return Err(RoxyCliError::Cancelled);
+    }
+    // Editors may add a newline at the end of the file
+    if contents.trim_end() == file.contents().trim_end() {
+      return Ok(None);
+    }
+
+    let edited = RoxyConfigFile::parse(file.path.clone(), contents.clone())
+      .and_then(|edited| edited.check().map(|_| edited));
+    match edited {
+      Ok(edited) => return Ok(Some((contents, edited))),
+      Err(reason) => {
+        let message = format!("roxy.json is invalid:\n{}", reason);
+        say(message.red());
+        problems = Some(message);
+      }
+    }
+  }
+}
+
+/// Asks for the settings roxy.json needs before roxy is usable, the same
+/// questions install asks
+pub fn setup(props: SetupCommand) -> Result<(), RoxyCliError> {
+  let mut file = config_file()?;
+  let settings = ask_settings(&file, &props.settings, !props.non_interactive)?;
+  let changed_keys = file.set_values(settings)?;
+  field("path", &file.path);
+  field("changed", &changed_keys);
+  if changed_keys.is_empty() {
+    say("No changes");
+    return Ok(());
+  }
+
+  write_config(&changed_keys, || {
+    file.write()?;
+    for key in &changed_keys {
+      let value = file.get(key)?;
+      say(format!("Set {} to {}", key, format_value(value)).green());
+    }
+    say(format!("Saved {:?}", file.path).green().bold());
+    Ok(())
+  })
+}
+
+pub fn logs(props: LogsCommand) -> Result<(), RoxyCliError> {
+  let install_info = InstallInfo::get()?;
   if !install_info.installed {
-    println!("{}", "Roxy is not installed".red().bold());
-    return;
+    return Err(RoxyCliError::NotInstalled);
   }
 
-  open::that_detached(install_info.path.join("roxy.log")).expect("Failed to open roxy.log");
+  for err in rotate_logs(&install_info) {
+    say(err.yellow());
+  }
+  print_logs(&install_info.roxy_log_path(), &props).map_err(RoxyCliError::Other)
 }
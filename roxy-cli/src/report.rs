@@ -0,0 +1,100 @@
+use colored::Colorize;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fmt::Display;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use crate::error::RoxyCliError;
+
+#[derive(Serialize)]
+struct ReportError {
+  kind: &'static str,
+  message: String,
+  hint: Option<String>,
+}
+
+/// The result of the command being run, printed as one JSON object with --json
+struct Report {
+  fields: Vec<(String, Value)>,
+  errors: Vec<ReportError>,
+  exit_code: Option<i32>,
+}
+
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+static REPORT: Mutex<Report> = Mutex::new(Report {
+  fields: Vec::new(),
+  errors: Vec::new(),
+  exit_code: None,
+});
+
+pub fn set_json_output(json: bool) {
+  JSON_OUTPUT.store(json, Ordering::SeqCst);
+}
+
+pub fn json_output() -> bool {
+  JSON_OUTPUT.load(Ordering::SeqCst)
+}
+
+/// Prints human readable output, which --json leaves out
+pub fn say(text: impl Display) {
+  if !json_output() {
+    println!("{}", text);
+  }
+}
+
+/// Records a field of the result, later values replace earlier ones
+pub fn field(key: &str, value: impl Serialize) {
+  let value = serde_json::to_value(value).unwrap_or(Value::Null);
+  let fields = &mut REPORT.lock().unwrap().fields;
+  match fields.iter_mut().find(|(existing, _)| existing == key) {
+    Some((_, existing)) => *existing = value,
+    None => fields.push((key.to_string(), value)),
+  }
+}
+
+/// Records the error a command failed with, printing it unless --json is set
+pub fn fail(err: &RoxyCliError) {
+  let hint = err.hint();
+  if !json_output() {
+    eprintln!("{}", err.to_string().red().bold());
+    if let Some(hint) = &hint {
+      eprintln!("{}", hint.yellow());
+    }
+  }
+
+  let mut report = REPORT.lock().unwrap();
+  report.errors.push(ReportError {
+    kind: err.kind(),
+    message: err.to_string(),
+    hint,
+  });
+  report.exit_code.get_or_insert(err.exit_code());
+}
+
+/// Exits with `code` instead of 0, for commands that pass on the server's
+/// exit code
+pub fn set_exit_code(code: i32) {
+  REPORT.lock().unwrap().exit_code = Some(code);
+}
+
+/// Prints the result with --json and returns the code to exit with
+pub fn finish(command: &str) -> i32 {
+  let mut report = REPORT.lock().unwrap();
+  let exit_code = report.exit_code.unwrap_or(0);
+
+  if json_output() {
+    let mut result = Map::new();
+    result.insert(String::from("command"), Value::from(command));
+    result.insert(String::from("ok"), Value::from(report.errors.is_empty()));
+    result.insert(String::from("exit_code"), Value::from(exit_code));
+    result.extend(report.fields.drain(..));
+    result.insert(
+      String::from("errors"),
+      serde_json::to_value(&report.errors).unwrap_or_default(),
+    );
+    println!("{}", Value::Object(result));
+  }
+
+  exit_code
+}
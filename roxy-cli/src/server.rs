@@ -0,0 +1,336 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus};
+#[cfg(target_os = "linux")]
+use std::sync::atomic::AtomicI32;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::build::Entrypoint;
+use crate::logrotate::rotate_logs;
+use crate::utils::InstallInfo;
+
+/// How often a foreground roxy-cli checks whether logs are due for rotation
+const LOG_ROTATION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Builds the command that launches the server.
+///
+/// The server reads `.env` and resolves its routes and views relative to the
+/// working directory, so it always runs from the roxy folder. `.env` is also
+/// passed as environment, with the `env` overrides from roxy-cli.json on top.
+pub fn server_command(install_info: &InstallInfo) -> Result<Command, String> {
+  let roxy_path = install_info.roxy_path();
+  let entrypoint = Entrypoint::resolve(&roxy_path).map_err(|err| err.to_string())?;
+
+  let mut command = entrypoint.command(install_info, &roxy_path);
+  command
+    .current_dir(&roxy_path)
+    .envs(read_dot_env(&roxy_path)?)
+    .envs(&install_info.env);
+
+  Ok(command)
+}
+
+fn read_dot_env(roxy_path: &Path) -> Result<Vec<(String, String)>, String> {
+  let path = roxy_path.join(".env");
+  let contents =
+    fs::read_to_string(&path).map_err(|err| format!("Failed to read {:?}: {}", path, err))?;
+
+  Ok(
+    contents
+      .lines()
+      .map(str::trim)
+      .filter(|line| !line.is_empty() && !line.starts_with('#'))
+      .filter_map(|line| line.split_once('='))
+      .map(|(key, value)| (key.trim().to_string(), value.to_string()))
+      .collect(),
+  )
+}
+
+/// Runs the server in the foreground with inherited stdio until it exits.
+///
+/// SIGTERM, SIGINT and SIGHUP are forwarded to the server instead of killing
+/// roxy-cli, so a supervisor sees the server's own exit code.
+/// Returns the code roxy-cli should exit with.
+pub fn run_foreground(install_info: &InstallInfo) -> Result<i32, String> {
+  catch_forwarded_signals();
+  let child = spawn_foreground(install_info)?;
+  let status = wait_forwarding_signals(install_info, child)?;
+
+  Ok(exit_code(status))
+}
+
+/// Spawns the server with inherited stdio and records it in the PID file.
+/// Call `catch_forwarded_signals` first.
+pub fn spawn_foreground(install_info: &InstallInfo) -> Result<Child, String> {
+  for err in rotate_logs(install_info) {
+    eprintln!("{}", err);
+  }
+
+  let mut command = server_command(install_info)?;
+  // Its own process group, so a terminal's Ctrl-C only reaches it once, through us
+  #[cfg(target_os = "linux")]
+  std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+  let child = command
+    .spawn()
+    .map_err(|err| format!("Failed to start roxy: {}", err))?;
+  if let Err(err) = PidFile::write(install_info, child.id()) {
+    eprintln!("{}", err);
+  }
+
+  Ok(child)
+}
+
+/// Waits for the server to exit, passing on any signal roxy-cli receives.
+/// Logs are rotated every now and then while waiting.
+pub fn wait_forwarding_signals(
+  install_info: &InstallInfo,
+  mut child: Child,
+) -> Result<ExitStatus, String> {
+  let mut last_rotation = Instant::now();
+  let status = loop {
+    match child.try_wait() {
+      Ok(Some(status)) => break status,
+      Ok(None) => {}
+      Err(err) => return Err(format!("Failed to wait for roxy: {}", err)),
+    }
+    forward_pending_signal(child.id());
+
+    if last_rotation.elapsed() >= LOG_ROTATION_INTERVAL {
+      for err in rotate_logs(install_info) {
+        eprintln!("{}", err);
+      }
+      last_rotation = Instant::now();
+    }
+    thread::sleep(Duration::from_millis(100));
+  };
+  PidFile::remove(install_info);
+
+  Ok(status)
+}
+
+/// Whether roxy-cli was asked to stop since `catch_forwarded_signals`
+pub fn stop_requested() -> bool {
+  STOP_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// The exit code, or 128 + the signal like a shell would report it
+pub fn exit_code(status: ExitStatus) -> i32 {
+  #[cfg(target_os = "linux")]
+  if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+    return 128 + signal;
+  }
+  status.code().unwrap_or(1)
+}
+
+/// The server process as recorded by `start`.
+///
+/// The process start time is stored next to the PID, so a recycled PID is not
+/// mistaken for roxy (only on Linux, Windows has no cheap way to read it).
+#[derive(Serialize, Deserialize)]
+pub struct PidFile {
+  pub pid: u32,
+  pub start_time: Option<u64>,
+}
+
+impl PidFile {
+  pub fn of(pid: u32) -> Self {
+    Self {
+      pid,
+      start_time: process_start_time(pid),
+    }
+  }
+
+  pub fn write(install_info: &InstallInfo, pid: u32) -> Result<(), String> {
+    let json = serde_json::to_string(&Self::of(pid)).map_err(|err| err.to_string())?;
+    fs::write(install_info.pid_path(), json)
+      .map_err(|err| format!("Failed to write roxy.pid: {}", err))
+  }
+
+  /// Reads the PID file, ignoring (and removing) it if the process is gone.
+  pub fn read(install_info: &InstallInfo) -> Option<Self> {
+    let contents = fs::read_to_string(install_info.pid_path()).ok()?;
+    let pid_file: Self = serde_json::from_str(&contents).ok()?;
+
+    if pid_file.is_alive() {
+      Some(pid_file)
+    } else {
+      Self::remove(install_info);
+      None
+    }
+  }
+
+  pub fn remove(install_info: &InstallInfo) {
+    fs::remove_file(install_info.pid_path()).ok();
+  }
+
+  pub fn is_alive(&self) -> bool {
+    if !process_exists(self.pid) {
+      return false;
+    }
+    match self.start_time {
+      Some(start_time) => process_start_time(self.pid) == Some(start_time),
+      None => true,
+    }
+  }
+
+  /// Asks the process to exit, then kills it if it is still around after `grace`.
+  /// Returns true once the process is confirmed gone.
+  pub fn terminate(&self, grace: Duration) -> bool {
+    send_signal(self.pid, false);
+    if self.wait_for_exit(grace) {
+      return true;
+    }
+
+    send_signal(self.pid, true);
+    self.wait_for_exit(Duration::from_secs(5))
+  }
+
+  /// How long the process has been running, if that can be told
+  pub fn uptime(&self) -> Option<Duration> {
+    if !self.is_alive() {
+      return None;
+    }
+    process_uptime(self.pid)
+  }
+
+  pub fn wait_for_exit(&self, timeout: Duration) -> bool {
+    let started = Instant::now();
+    while started.elapsed() < timeout {
+      if !self.is_alive() {
+        return true;
+      }
+      thread::sleep(Duration::from_millis(100));
+    }
+    !self.is_alive()
+  }
+}
+
+/// Clock ticks since boot at which the process started, from /proc/<pid>/stat
+#[cfg(target_os = "linux")]
+fn process_start_time(pid: u32) -> Option<u64> {
+  let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+  // The command name may contain spaces, so count fields from after it.
+  // starttime is field 22, the first field after the name is field 3
+  stat
+    .rsplit_once(')')?
+    .1
+    .split_whitespace()
+    .nth(19)?
+    .parse()
+    .ok()
+}
+
+#[cfg(target_os = "windows")]
+fn process_start_time(_pid: u32) -> Option<u64> {
+  None
+}
+
+#[cfg(target_os = "linux")]
+fn process_uptime(pid: u32) -> Option<Duration> {
+  let uptime: f64 = fs::read_to_string("/proc/uptime")
+    .ok()?
+    .split_whitespace()
+    .next()?
+    .parse()
+    .ok()?;
+  let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+  if ticks_per_sec <= 0 {
+    return None;
+  }
+  let started = process_start_time(pid)? as f64 / ticks_per_sec as f64;
+  Some(Duration::from_secs_f64((uptime - started).max(0.0)))
+}
+
+#[cfg(target_os = "windows")]
+fn process_uptime(_pid: u32) -> Option<Duration> {
+  None
+}
+
+#[cfg(target_os = "linux")]
+fn process_exists(pid: u32) -> bool {
+  // A zombie still has a /proc entry but is as good as gone
+  match fs::read_to_string(format!("/proc/{}/stat", pid)) {
+    Ok(stat) => stat
+      .rsplit_once(')')
+      .and_then(|(_, rest)| rest.split_whitespace().next())
+      .is_some_and(|state| state != "Z"),
+    Err(_) => false,
+  }
+}
+
+#[cfg(target_os = "windows")]
+fn process_exists(pid: u32) -> bool {
+  Command::new("tasklist")
+    .args(["/FI", &format!("PID eq {}", pid), "/NH"])
+    .output()
+    .map(|output| String::from_utf8_lossy(&output.stdout).contains(&pid.to_string()))
+    .unwrap_or(false)
+}
+
+/// SIGTERM, or SIGKILL if `force` is set.
+#[cfg(target_os = "linux")]
+fn send_signal(pid: u32, force: bool) {
+  signal_group(pid, if force { libc::SIGKILL } else { libc::SIGTERM });
+}
+
+/// roxy runs in its own process group, so the whole group is signalled to
+/// also reach npm's children.
+#[cfg(target_os = "linux")]
+fn signal_group(pid: u32, signal: libc::c_int) {
+  unsafe {
+    libc::kill(-(pid as libc::pid_t), signal);
+  }
+}
+
+#[cfg(target_os = "windows")]
+fn send_signal(pid: u32, force: bool) {
+  let pid = pid.to_string();
+  let mut args = vec!["/PID", pid.as_str(), "/T"];
+  if force {
+    args.push("/F");
+  }
+  Command::new("taskkill").args(args).output().ok();
+}
+
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Last signal received by `run`, 0 if there is none to forward
+#[cfg(target_os = "linux")]
+static PENDING_SIGNAL: AtomicI32 = AtomicI32::new(0);
+
+#[cfg(target_os = "linux")]
+extern "C" fn store_signal(signal: libc::c_int) {
+  STOP_REQUESTED.store(true, Ordering::SeqCst);
+  PENDING_SIGNAL.store(signal, Ordering::SeqCst);
+}
+
+#[cfg(target_os = "linux")]
+pub fn catch_forwarded_signals() {
+  for signal in [libc::SIGTERM, libc::SIGINT, libc::SIGHUP] {
+    unsafe {
+      libc::signal(signal, store_signal as *const () as libc::sighandler_t);
+    }
+  }
+}
+
+#[cfg(target_os = "linux")]
+fn forward_pending_signal(pid: u32) {
+  let signal = PENDING_SIGNAL.swap(0, Ordering::SeqCst);
+  if signal != 0 {
+    signal_group(pid, signal);
+  }
+}
+
+/// Ctrl-C reaches every process on the console already, roxy-cli only has
+/// to outlive the server.
+#[cfg(target_os = "windows")]
+pub fn catch_forwarded_signals() {
+  ctrlc::set_handler(|| STOP_REQUESTED.store(true, Ordering::SeqCst)).ok();
+}
+
+#[cfg(target_os = "windows")]
+fn forward_pending_signal(_pid: u32) {}
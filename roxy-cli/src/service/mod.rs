@@ -0,0 +1,158 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::{env, fs};
+
+use crate::args::ServiceManagerKind;
+use crate::utils::InstallInfo;
+
+mod openrc;
+mod registry;
+mod runit;
+mod systemd;
+mod xdg;
+
+/// Something that can start roxy on boot or login.
+///
+/// Backends that supervise roxy themselves run `roxy-cli run`, the others
+/// run `roxy-cli start`.
+pub trait ServiceManager {
+  fn kind(&self) -> ServiceManagerKind;
+
+  /// Whether this manager can be used on this machine
+  fn is_available(&self) -> bool;
+
+  /// Where the unit, script or entry is written to
+  fn location(&self) -> String;
+
+  /// Writes and enables the service, running `roxy_cli`.
+  fn install(&self, roxy_cli: &Path) -> Result<(), String>;
+
+  fn uninstall(&self) -> Result<(), String>;
+
+  fn is_enabled(&self) -> bool;
+
+  /// What the manager reports about the service, if it tracks it at all
+  fn status(&self) -> Option<String> {
+    None
+  }
+
+  /// Anything the user still has to do for the service to start
+  fn hint(&self) -> Option<String> {
+    None
+  }
+}
+
+pub fn backend(kind: ServiceManagerKind) -> Box<dyn ServiceManager> {
+  match kind {
+    ServiceManagerKind::SystemdUser => Box::new(systemd::Systemd { user: true }),
+    ServiceManagerKind::SystemdSystem => Box::new(systemd::Systemd { user: false }),
+    ServiceManagerKind::Openrc => Box::new(openrc::OpenRc),
+    ServiceManagerKind::Runit => Box::new(runit::Runit),
+    ServiceManagerKind::Xdg => Box::new(xdg::XdgAutostart),
+    ServiceManagerKind::Registry => Box::new(registry::Registry),
+  }
+}
+
+/// The manager to use when none was given: the one roxy is already enabled
+/// with, or else the preferred available one. System-wide managers are only
+/// considered when running as root.
+pub fn detect() -> Option<Box<dyn ServiceManager>> {
+  let mut candidates = Vec::new();
+  if cfg!(target_os = "windows") {
+    candidates.push(ServiceManagerKind::Registry);
+  } else {
+    if is_root() {
+      candidates.extend([
+        ServiceManagerKind::SystemdSystem,
+        ServiceManagerKind::Openrc,
+        ServiceManagerKind::Runit,
+      ]);
+    }
+    candidates.extend([ServiceManagerKind::SystemdUser, ServiceManagerKind::Xdg]);
+  }
+
+  let available: Vec<_> = candidates
+    .into_iter()
+    .map(backend)
+    .filter(|manager| manager.is_available())
+    .collect();
+  let enabled = available.iter().position(|manager| manager.is_enabled());
+
+  available.into_iter().nth(enabled.unwrap_or(0))
+}
+
+/// Copies roxy-cli next to its config, so the service keeps working if the
+/// downloaded binary is moved or deleted
+pub fn install_binary() -> Result<PathBuf, String> {
+  let self_path =
+    env::current_exe().map_err(|err| format!("Failed to get current executable path: {}", err))?;
+  let dest = InstallInfo::get_roxy_cli_path()
+    .map_err(|err| err.to_string())?
+    .join(if cfg!(target_os = "windows") {
+      "roxy-cli.exe"
+    } else {
+      "roxy-cli"
+    });
+
+  if self_path != dest {
+    fs::copy(self_path, &dest).map_err(|err| format!("Failed to copy executable: {}", err))?;
+  }
+  Ok(dest)
+}
+
+#[cfg(target_os = "linux")]
+fn is_root() -> bool {
+  unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(target_os = "windows")]
+fn is_root() -> bool {
+  false
+}
+
+/// Runs `program`, returning its stdout, or its stderr as the error
+fn run(program: &str, args: &[&str]) -> Result<String, String> {
+  let output = Command::new(program)
+    .args(args)
+    .output()
+    .map_err(|err| format!("Failed to run {}: {}", program, err))?;
+
+  if output.status.success() {
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+  } else {
+    Err(format!(
+      "`{} {}` failed: {}",
+      program,
+      args.join(" "),
+      String::from_utf8_lossy(&output.stderr).trim()
+    ))
+  }
+}
+
+fn write_file(path: &Path, contents: &str, executable: bool) -> Result<(), String> {
+  if let Some(parent) = path.parent() {
+    fs::create_dir_all(parent).map_err(|err| format!("Failed to create {:?}: {}", parent, err))?;
+  }
+  fs::write(path, contents).map_err(|err| format!("Failed to write {:?}: {}", path, err))?;
+
+  #[cfg(unix)]
+  if executable {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+      .map_err(|err| format!("Failed to make {:?} executable: {}", path, err))?;
+  }
+  #[cfg(not(unix))]
+  let _ = executable;
+
+  Ok(())
+}
+
+/// Quotes `value` for a POSIX shell script
+fn shell_quote(value: &str) -> String {
+  format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// HOME for services that don't get one, so `roxy-cli run` finds its config
+fn home() -> String {
+  env::var("HOME").unwrap_or_default()
+}
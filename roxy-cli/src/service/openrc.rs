@@ -0,0 +1,67 @@
+use std::path::Path;
+
+use super::{home, run, shell_quote, write_file, ServiceManager};
+use crate::args::ServiceManagerKind;
+use crate::utils::remove_file;
+
+const SCRIPT_PATH: &str = "/etc/init.d/roxy";
+const RUNLEVEL: &str = "default";
+
+/// An OpenRC init script running `roxy-cli run`, e.g. on Alpine
+pub struct OpenRc;
+
+impl ServiceManager for OpenRc {
+  fn kind(&self) -> ServiceManagerKind {
+    ServiceManagerKind::Openrc
+  }
+
+  fn is_available(&self) -> bool {
+    Path::new("/sbin/openrc-run").exists() || Path::new("/usr/sbin/openrc-run").exists()
+  }
+
+  fn location(&self) -> String {
+    SCRIPT_PATH.to_string()
+  }
+
+  fn install(&self, roxy_cli: &Path) -> Result<(), String> {
+    let script = format!(
+      "#!/sbin/openrc-run\n\
+       \n\
+       name=\"roxy\"\n\
+       description=\"Roxy\"\n\
+       command={}\n\
+       command_args=\"run\"\n\
+       command_background=true\n\
+       pidfile=\"/run/${{RC_SVCNAME}}.pid\"\n\
+       export HOME={}\n\
+       \n\
+       depend() {{\n\
+       \tneed net\n\
+       }}\n",
+      shell_quote(&roxy_cli.to_string_lossy()),
+      shell_quote(&home())
+    );
+
+    write_file(Path::new(SCRIPT_PATH), &script, true)?;
+    run("rc-update", &["add", "roxy", RUNLEVEL]).map(|_| ())
+  }
+
+  fn uninstall(&self) -> Result<(), String> {
+    run("rc-update", &["del", "roxy", RUNLEVEL])?;
+    remove_file(Path::new(SCRIPT_PATH)).map_err(|err| err.to_string())
+  }
+
+  fn is_enabled(&self) -> bool {
+    Path::new("/etc/runlevels")
+      .join(RUNLEVEL)
+      .join("roxy")
+      .exists()
+  }
+
+  fn status(&self) -> Option<String> {
+    Some(match run("rc-service", &["roxy", "status"]) {
+      Ok(output) => output,
+      Err(_) => String::from("stopped"),
+    })
+  }
+}
@@ -0,0 +1,41 @@
+use std::path::Path;
+
+use super::{run, ServiceManager};
+use crate::args::ServiceManagerKind;
+
+const RUN_KEY: &str = "HKCU\\Software\\Microsoft\\Windows\\CurrentVersion\\Run";
+
+/// The Windows Run key, running `roxy-cli start` on login
+pub struct Registry;
+
+impl ServiceManager for Registry {
+  fn kind(&self) -> ServiceManagerKind {
+    ServiceManagerKind::Registry
+  }
+
+  fn is_available(&self) -> bool {
+    cfg!(target_os = "windows")
+  }
+
+  fn location(&self) -> String {
+    format!("{}\\Roxy", RUN_KEY)
+  }
+
+  fn install(&self, roxy_cli: &Path) -> Result<(), String> {
+    // {:?} nicely provides us with quotation marks lol
+    let command = format!("{:?} start", roxy_cli);
+    run(
+      "reg",
+      &["add", RUN_KEY, "/v", "Roxy", "/d", command.as_str(), "/f"],
+    )
+    .map(|_| ())
+  }
+
+  fn uninstall(&self) -> Result<(), String> {
+    run("reg", &["delete", RUN_KEY, "/v", "Roxy", "/f"]).map(|_| ())
+  }
+
+  fn is_enabled(&self) -> bool {
+    run("reg", &["query", RUN_KEY, "/v", "Roxy"]).is_ok()
+  }
+}
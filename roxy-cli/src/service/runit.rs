@@ -0,0 +1,85 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::{home, run, shell_quote, write_file, ServiceManager};
+use crate::args::ServiceManagerKind;
+use crate::utils::remove_file;
+
+/// Where the service definition lives, linked into the service directory to enable it
+const SV_PATH: &str = "/etc/sv/roxy";
+
+/// A runit service running `roxy-cli run`, e.g. in containers or on Void
+pub struct Runit;
+
+impl Runit {
+  /// The directory runsvdir watches, which differs between distributions
+  fn service_dir() -> Option<PathBuf> {
+    if let Ok(dir) = env::var("SVDIR") {
+      return Some(PathBuf::from(dir));
+    }
+    ["/var/service", "/etc/service", "/service"]
+      .iter()
+      .map(PathBuf::from)
+      .find(|dir| dir.is_dir())
+  }
+
+  fn link_path() -> Option<PathBuf> {
+    Self::service_dir().map(|dir| dir.join("roxy"))
+  }
+}
+
+impl ServiceManager for Runit {
+  fn kind(&self) -> ServiceManagerKind {
+    ServiceManagerKind::Runit
+  }
+
+  fn is_available(&self) -> bool {
+    Self::service_dir().is_some()
+  }
+
+  fn location(&self) -> String {
+    SV_PATH.to_string()
+  }
+
+  fn install(&self, roxy_cli: &Path) -> Result<(), String> {
+    let link_path = Self::link_path().ok_or("No runit service directory found")?;
+    let script = format!(
+      "#!/bin/sh\n\
+       export HOME={}\n\
+       exec {} run 2>&1\n",
+      shell_quote(&home()),
+      shell_quote(&roxy_cli.to_string_lossy())
+    );
+    write_file(&Path::new(SV_PATH).join("run"), &script, true)?;
+
+    #[cfg(unix)]
+    if !link_path.exists() {
+      std::os::unix::fs::symlink(SV_PATH, &link_path)
+        .map_err(|err| format!("Failed to link {:?}: {}", link_path, err))?;
+    }
+    #[cfg(not(unix))]
+    let _ = link_path;
+
+    Ok(())
+  }
+
+  fn uninstall(&self) -> Result<(), String> {
+    if let Some(link_path) = Self::link_path() {
+      // runsvdir stops the service once the link is gone
+      remove_file(&link_path).map_err(|err| err.to_string())?;
+    }
+    remove_file(&Path::new(SV_PATH).join("run")).map_err(|err| err.to_string())?;
+    fs::remove_dir_all(SV_PATH).ok();
+    Ok(())
+  }
+
+  fn is_enabled(&self) -> bool {
+    Self::link_path().is_some_and(|link_path| link_path.exists())
+  }
+
+  fn status(&self) -> Option<String> {
+    let link_path = Self::link_path()?;
+    run("sv", &["status", &link_path.to_string_lossy()]).ok()
+  }
+}
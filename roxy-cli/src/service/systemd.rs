@@ -0,0 +1,139 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use super::{home, run, write_file, ServiceManager};
+use crate::args::ServiceManagerKind;
+use crate::utils::remove_file;
+
+const UNIT: &str = "roxy.service";
+
+/// A systemd unit running `roxy-cli run`, either per user or system-wide
+pub struct Systemd {
+  pub user: bool,
+}
+
+impl Systemd {
+  fn unit_path(&self) -> PathBuf {
+    if self.user {
+      let config_path = match env::var("XDG_CONFIG_HOME") {
+        Ok(path) if !path.is_empty() => PathBuf::from(path),
+        _ => PathBuf::from(home()).join(".config"),
+      };
+      config_path.join("systemd").join("user").join(UNIT)
+    } else {
+      PathBuf::from("/etc/systemd/system").join(UNIT)
+    }
+  }
+
+  fn systemctl(&self, args: &[&str]) -> Result<String, String> {
+    let mut all_args = Vec::new();
+    if self.user {
+      all_args.push("--user");
+    }
+    all_args.extend_from_slice(args);
+    run("systemctl", &all_args)
+  }
+}
+
+impl ServiceManager for Systemd {
+  fn kind(&self) -> ServiceManagerKind {
+    if self.user {
+      ServiceManagerKind::SystemdUser
+    } else {
+      ServiceManagerKind::SystemdSystem
+    }
+  }
+
+  fn is_available(&self) -> bool {
+    if self.user {
+      // Fails without a running user manager, e.g. in containers
+      self.systemctl(&["show-environment"]).is_ok()
+    } else {
+      Path::new("/run/systemd/system").exists()
+    }
+  }
+
+  fn location(&self) -> String {
+    self.unit_path().display().to_string()
+  }
+
+  fn install(&self, roxy_cli: &Path) -> Result<(), String> {
+    // `roxy-cli run` exits with 128 + the signal, so being stopped with
+    // SIGHUP, SIGINT or SIGTERM would otherwise leave the unit failed
+    let unit = if self.user {
+      format!(
+        "[Unit]\n\
+         Description=Roxy\n\
+         \n\
+         [Service]\n\
+         ExecStart={:?} run\n\
+         Restart=on-failure\n\
+         RestartSec=5\n\
+         SuccessExitStatus=129 130 143\n\
+         \n\
+         [Install]\n\
+         WantedBy=default.target\n",
+        roxy_cli
+      )
+    } else {
+      format!(
+        "[Unit]\n\
+         Description=Roxy\n\
+         Wants=network-online.target\n\
+         After=network-online.target\n\
+         \n\
+         [Service]\n\
+         Environment=HOME={:?}\n\
+         ExecStart={:?} run\n\
+         Restart=on-failure\n\
+         RestartSec=5\n\
+         SuccessExitStatus=129 130 143\n\
+         \n\
+         [Install]\n\
+         WantedBy=multi-user.target\n",
+        home(),
+        roxy_cli
+      )
+    };
+
+    write_file(&self.unit_path(), &unit, false)?;
+    self.systemctl(&["daemon-reload"])?;
+    self.systemctl(&["enable", UNIT]).map(|_| ())
+  }
+
+  fn uninstall(&self) -> Result<(), String> {
+    self.systemctl(&["disable", UNIT])?;
+    remove_file(&self.unit_path()).map_err(|err| err.to_string())?;
+    self.systemctl(&["daemon-reload"]).map(|_| ())
+  }
+
+  fn is_enabled(&self) -> bool {
+    self.unit_path().exists() && self.systemctl(&["is-enabled", "--quiet", UNIT]).is_ok()
+  }
+
+  fn status(&self) -> Option<String> {
+    // is-active exits non-zero for anything but active, the output is still the state
+    Some(
+      self
+        .systemctl(&["is-active", UNIT])
+        .unwrap_or_else(|_| String::from("inactive")),
+    )
+  }
+
+  fn hint(&self) -> Option<String> {
+    if !self.user {
+      return None;
+    }
+
+    let user = env::var("USER").ok()?;
+    let linger = run("loginctl", &["show-user", &user, "--property=Linger"]).ok()?;
+    if linger == "Linger=yes" {
+      return None;
+    }
+
+    Some(format!(
+      "Roxy will only start once you log in. To start it at boot, run:\n  loginctl enable-linger {}",
+      user
+    ))
+  }
+}
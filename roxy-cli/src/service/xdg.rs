@@ -0,0 +1,56 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use super::{home, write_file, ServiceManager};
+use crate::args::ServiceManagerKind;
+use crate::utils::remove_file;
+
+/// An XDG autostart entry running `roxy-cli start` on desktop login,
+/// for desktops without a systemd user manager
+pub struct XdgAutostart;
+
+impl XdgAutostart {
+  fn entry_path() -> PathBuf {
+    let config_path = match env::var("XDG_CONFIG_HOME") {
+      Ok(path) if !path.is_empty() => PathBuf::from(path),
+      _ => PathBuf::from(home()).join(".config"),
+    };
+    config_path.join("autostart").join("roxy.desktop")
+  }
+}
+
+impl ServiceManager for XdgAutostart {
+  fn kind(&self) -> ServiceManagerKind {
+    ServiceManagerKind::Xdg
+  }
+
+  fn is_available(&self) -> bool {
+    cfg!(target_os = "linux")
+  }
+
+  fn location(&self) -> String {
+    Self::entry_path().display().to_string()
+  }
+
+  fn install(&self, roxy_cli: &Path) -> Result<(), String> {
+    let entry = format!(
+      "[Desktop Entry]\n\
+       Type=Application\n\
+       Name=Roxy\n\
+       Comment=Starts the roxy server\n\
+       Exec={:?} start\n\
+       Terminal=false\n\
+       X-GNOME-Autostart-enabled=true\n",
+      roxy_cli
+    );
+    write_file(&Self::entry_path(), &entry, false)
+  }
+
+  fn uninstall(&self) -> Result<(), String> {
+    remove_file(&Self::entry_path()).map_err(|err| err.to_string())
+  }
+
+  fn is_enabled(&self) -> bool {
+    Self::entry_path().exists()
+  }
+}
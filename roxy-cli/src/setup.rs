@@ -0,0 +1,218 @@
+use colored::Colorize;
+use reqwest::Url;
+use serde_json::Value;
+use std::fs::File;
+use std::net::TcpListener;
+
+use crate::args::SetupSettings;
+use crate::config::{RoxyConfig, RoxyConfigFile};
+use crate::console::{ask, ask_bool, reject_answer};
+use crate::error::RoxyCliError;
+use crate::report::say;
+use crate::utils::is_running;
+
+/// How far past a taken port to look for a free one
+const PORT_SEARCH_RANGE: u16 = 100;
+
+/// The settings an operator has to look at before the first start, asked for
+/// one by one or, with `interactive` off, taken from `flags`.
+/// Flags that were passed are never asked for.
+pub fn ask_settings(
+  file: &RoxyConfigFile,
+  flags: &SetupSettings,
+  interactive: bool,
+) -> Result<Vec<(&'static str, Value)>, RoxyCliError> {
+  // Keys that are missing or broken start out as their default
+  let current = file.config_or_defaults();
+  let mut settings = Vec::new();
+
+  // The server's placeholder is never right, so it is not offered as the default
+  let placeholder = current.url == RoxyConfig::default().url;
+  let url = match &flags.url {
+    Some(url) => Some(parse_url(url).map_err(RoxyCliError::InvalidArgument)?),
+    None if interactive => Some(ask_until_valid(
+      "Public URL roxy is reached at",
+      (!placeholder).then(|| current.url.clone()),
+      parse_url,
+    )?),
+    None => {
+      if placeholder {
+        say(format!("`url` is still {}, set it with --url", current.url).yellow());
+      }
+      None
+    }
+  };
+  if let Some(url) = url {
+    settings.push(("url", Value::from(url)));
+  }
+
+  let port = match flags.port {
+    Some(port) => {
+      check_port(port, &current).map_err(RoxyCliError::InvalidArgument)?;
+      Some(port)
+    }
+    None if interactive => {
+      let mut default = current.port;
+      if let Err(reason) = check_port(default, &current) {
+        reject_answer(&reason);
+        default = free_port(default).unwrap_or(default);
+      }
+      Some(ask_until_valid(
+        "Port",
+        Some(default.to_string()),
+        |port| {
+          let port = port
+            .parse::<u16>()
+            .ok()
+            .filter(|port| *port != 0)
+            .ok_or_else(|| String::from("The port has to be between 1 and 65535"))?;
+          check_port(port, &current).map(|_| port)
+        },
+      )?)
+    }
+    None => {
+      if let Err(reason) = check_port(current.port, &current) {
+        say(format!("{}, pick another one with --port", reason).yellow());
+      }
+      None
+    }
+  };
+  if let Some(port) = port {
+    settings.push(("port", Value::from(port)));
+  }
+
+  let use_https = match flags.use_https {
+    Some(use_https) => use_https,
+    None if interactive => ask_bool(
+      "Serve HTTPS directly? Say no if a reverse proxy handles it",
+      current.use_https,
+    )?,
+    None => current.use_https,
+  };
+  settings.push(("useHttps", Value::from(use_https)));
+
+  for (key, flag_name, flag, current, prompt) in [
+    (
+      "sslCertPath",
+      "--ssl-cert-path",
+      &flags.ssl_cert_path,
+      &current.ssl_cert_path,
+      "Path to the SSL certificate",
+    ),
+    (
+      "sslKeyPath",
+      "--ssl-key-path",
+      &flags.ssl_key_path,
+      &current.ssl_key_path,
+      "Path to the SSL private key",
+    ),
+  ] {
+    let path = match flag {
+      Some(path) => Some(check_readable(path).map_err(RoxyCliError::InvalidArgument)?),
+      None if interactive && use_https => Some(ask_until_valid(prompt, current.clone(), |path| {
+        check_readable(path)
+      })?),
+      None if use_https => match current {
+        Some(path) => Some(check_readable(path).map_err(RoxyCliError::InvalidArgument)?),
+        None => {
+          return Err(RoxyCliError::InvalidArgument(format!(
+            "--use-https needs {}",
+            flag_name
+          )))
+        }
+      },
+      None => None,
+    };
+    if let Some(path) = path {
+      settings.push((key, Value::from(path)));
+    }
+  }
+
+  let is_proxied = match flags.is_proxied {
+    Some(is_proxied) => is_proxied,
+    None if interactive => ask_bool(
+      "Is roxy behind a reverse proxy like nginx?",
+      current.is_proxied,
+    )?,
+    None => current.is_proxied,
+  };
+  settings.push(("isProxied", Value::from(is_proxied)));
+
+  let allow_registrations = match flags.allow_registrations {
+    Some(allow_registrations) => allow_registrations,
+    None if interactive => ask_bool("Allow anyone to register?", current.allow_registrations)?,
+    None => current.allow_registrations,
+  };
+  settings.push(("allowRegistrations", Value::from(allow_registrations)));
+
+  Ok(settings)
+}
+
+/// Asks until `parse` accepts the answer
+fn ask_until_valid<T>(
+  prompt: &str,
+  default: Option<String>,
+  parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<T, RoxyCliError> {
+  loop {
+    let answer = ask(prompt, default.as_deref())?;
+    match parse(&answer) {
+      Ok(value) => return Ok(value),
+      Err(reason) => reject_answer(&reason),
+    }
+  }
+}
+
+/// The server only needs `new URL(url)` to work, but anything other than
+/// http(s) with a host would make every link it hands out broken
+fn parse_url(url: &str) -> Result<String, String> {
+  let url = url.trim();
+  if url.is_empty() {
+    return Err(String::from(
+      "A URL is needed, e.g. https://roxy.example.com",
+    ));
+  }
+  match Url::parse(url) {
+    Ok(parsed) if ["http", "https"].contains(&parsed.scheme()) && parsed.has_host() => {
+      Ok(url.to_string())
+    }
+    _ => Err(format!(
+      "{:?} is not a http(s) URL, e.g. https://roxy.example.com",
+      url
+    )),
+  }
+}
+
+/// Roxy listens on all interfaces, so the port has to be free on all of them.
+/// The port a running roxy already listens on is fine.
+fn check_port(port: u16, current: &RoxyConfig) -> Result<(), String> {
+  if port_is_free(port) || (port == current.port && is_running()) {
+    return Ok(());
+  }
+  match free_port(port) {
+    Some(free) => Err(format!("Port {} is already in use, {} is free", port, free)),
+    None => Err(format!("Port {} is already in use", port)),
+  }
+}
+
+fn port_is_free(port: u16) -> bool {
+  TcpListener::bind(("0.0.0.0", port)).is_ok()
+}
+
+/// The first free port after `port`
+fn free_port(port: u16) -> Option<u16> {
+  (port.saturating_add(1)..=port.saturating_add(PORT_SEARCH_RANGE)).find(|port| port_is_free(*port))
+}
+
+/// Roxy reads the certificate and key on startup and crashes if it can not
+fn check_readable(path: &str) -> Result<String, String> {
+  let path = path.trim();
+  if path.is_empty() {
+    return Err(String::from("A path is needed to serve HTTPS"));
+  }
+  match File::open(path).and_then(|file| file.metadata()) {
+    Ok(metadata) if metadata.is_file() => Ok(path.to_string()),
+    Ok(_) => Err(format!("{:?} is not a file", path)),
+    Err(err) => Err(format!("{:?} can not be read: {}", path, err)),
+  }
+}
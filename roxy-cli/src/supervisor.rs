@@ -0,0 +1,258 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::process::{self, ExitStatus};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::args::WatchdogOptions;
+use crate::server::{
+  catch_forwarded_signals, exit_code, spawn_foreground, stop_requested, wait_forwarding_signals,
+  PidFile,
+};
+use crate::utils::{sleep_unless, InstallInfo};
+use crate::watchdog::Watchdog;
+
+/// Delay before the first restart, doubled for every crash in a row
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+/// A server that stayed up this long is healthy again, which resets the backoff
+const STABLE_RUN: Duration = Duration::from_secs(60);
+/// How long a hung server gets to exit after SIGTERM before it is killed
+const HANG_GRACE_PERIOD: Duration = Duration::from_secs(5);
+
+/// Written to supervisor.json so `status` can report on the supervisor.
+#[derive(Serialize, Deserialize)]
+pub struct SupervisorState {
+  pub process: PidFile,
+  pub restarts: u32,
+  pub last_crash: Option<Crash>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Crash {
+  pub time: DateTime<Utc>,
+  pub reason: String,
+}
+
+impl SupervisorState {
+  pub fn read(install_info: &InstallInfo) -> Option<Self> {
+    let contents = fs::read_to_string(install_info.supervisor_path()).ok()?;
+    serde_json::from_str(&contents).ok()
+  }
+
+  fn write(&self, install_info: &InstallInfo) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+    fs::write(install_info.supervisor_path(), json)
+      .map_err(|err| format!("Failed to write supervisor.json: {}", err))
+  }
+}
+
+/// Runs the server in the foreground and restarts it whenever it crashes.
+///
+/// Restarts back off exponentially, and after `max_restarts` crashes in a row
+/// the supervisor gives up. A clean exit, e.g. after `roxy-cli stop`, or a
+/// signal to the supervisor ends supervision. With a `watchdog`, a server that
+/// stops responding is killed and restarted like a crashed one.
+/// Returns the code roxy-cli should exit with.
+pub fn supervise(
+  install_info: &InstallInfo,
+  max_restarts: u32,
+  watchdog: Option<WatchdogOptions>,
+) -> Result<i32, String> {
+  let result = supervise_until_done(install_info, max_restarts, watchdog);
+  // Otherwise `status` would report on this run for as long as no other
+  // supervisor replaces it
+  fs::remove_file(install_info.supervisor_path()).ok();
+  result
+}
+
+fn supervise_until_done(
+  install_info: &InstallInfo,
+  max_restarts: u32,
+  watchdog: Option<WatchdogOptions>,
+) -> Result<i32, String> {
+  catch_forwarded_signals();
+
+  let mut state = SupervisorState {
+    process: PidFile::of(process::id()),
+    restarts: 0,
+    last_crash: None,
+  };
+  state.write(install_info)?;
+  log(install_info, "Log", "Supervisor started");
+
+  let mut crashes = 0;
+  loop {
+    let started = Instant::now();
+    let child = spawn_foreground(install_info)?;
+    let watched = watchdog
+      .clone()
+      .map(|options| WatchedChild::spawn(install_info.clone(), options, child.id()));
+    let pid = child.id();
+    let status = wait_forwarding_signals(install_info, child)?;
+    let hang = watched
+      .and_then(WatchedChild::finish)
+      .or_else(|| take_hang_kill(install_info, pid));
+
+    if hang.is_none() && (stop_requested() || stopped_on_purpose(status)) {
+      log(
+        install_info,
+        "Log",
+        &format!("roxy stopped ({}), supervisor exiting", status),
+      );
+      return Ok(exit_code(status));
+    }
+
+    if started.elapsed() >= STABLE_RUN {
+      crashes = 0;
+    }
+    crashes += 1;
+    let reason = match hang {
+      Some(reason) => format!("hung, {}", reason),
+      None => status.to_string(),
+    };
+    state.last_crash = Some(Crash {
+      time: Utc::now(),
+      reason: reason.clone(),
+    });
+
+    if crashes > max_restarts {
+      state.write(install_info)?;
+      log(
+        install_info,
+        "Err",
+        &format!(
+          "roxy crashed ({}), giving up after {} restarts in a row",
+          reason, max_restarts
+        ),
+      );
+      // A hung server killed by the watchdog may still have exited cleanly
+      return Ok(exit_code(status).max(1));
+    }
+
+    let backoff = INITIAL_BACKOFF
+      .saturating_mul(2u32.saturating_pow(crashes - 1))
+      .min(MAX_BACKOFF);
+    state.restarts += 1;
+    state.write(install_info)?;
+    log(
+      install_info,
+      "Warn",
+      &format!(
+        "roxy crashed ({}), restarting in {}s ({}/{})",
+        reason,
+        backoff.as_secs(),
+        crashes,
+        max_restarts
+      ),
+    );
+
+    if !sleep_unless(backoff, stop_requested) {
+      log(install_info, "Log", "Stopped while waiting to restart roxy");
+      return Ok(0);
+    }
+  }
+}
+
+/// A watchdog thread for one server process
+struct WatchedChild {
+  done: Arc<AtomicBool>,
+  thread: JoinHandle<Option<String>>,
+}
+
+impl WatchedChild {
+  /// Probes the server until `finish` is called. If the server hangs, it
+  /// captures diagnostics and kills the server.
+  fn spawn(install_info: InstallInfo, options: WatchdogOptions, pid: u32) -> Self {
+    let done = Arc::new(AtomicBool::new(false));
+    let thread = thread::spawn({
+      let done = done.clone();
+      move || {
+        let mut watchdog = Watchdog::new(options);
+        while sleep_unless(watchdog.interval(), || done.load(Ordering::SeqCst)) {
+          if let Some(reason) = watchdog.probe(pid) {
+            match watchdog.capture_diagnostics(&install_info, &reason) {
+              Ok(path) => log(
+                &install_info,
+                "Warn",
+                &format!("roxy is hung ({}), diagnostics saved to {:?}", reason, path),
+              ),
+              Err(err) => log(&install_info, "Err", &err),
+            }
+            PidFile::of(pid).terminate(HANG_GRACE_PERIOD);
+            return Some(reason);
+          }
+        }
+        None
+      }
+    });
+
+    Self { done, thread }
+  }
+
+  /// Stops the watchdog, returning why the server was killed if it hung
+  fn finish(self) -> Option<String> {
+    self.done.store(true, Ordering::SeqCst);
+    self.thread.join().ok().flatten()
+  }
+}
+
+/// Called by `roxy-cli watchdog` before it kills a hung server, so the
+/// supervisor restarts it instead of taking the SIGTERM for `roxy-cli stop`
+pub fn mark_hang_kill(install_info: &InstallInfo, pid: u32, reason: &str) -> Result<(), String> {
+  fs::write(
+    install_info.hang_kill_path(),
+    format!("{}\n{}", pid, reason),
+  )
+  .map_err(|err| {
+    format!(
+      "Failed to write {:?}: {}",
+      install_info.hang_kill_path(),
+      err
+    )
+  })
+}
+
+/// Why the watchdog killed `pid`, if it did
+fn take_hang_kill(install_info: &InstallInfo, pid: u32) -> Option<String> {
+  let contents = fs::read_to_string(install_info.hang_kill_path()).ok()?;
+  let (killed, reason) = contents.split_once('\n')?;
+  if killed.parse() != Ok(pid) {
+    return None;
+  }
+  fs::remove_file(install_info.hang_kill_path()).ok();
+  Some(reason.to_string())
+}
+
+/// `roxy-cli stop` ends the server cleanly, or with SIGTERM as a fallback
+fn stopped_on_purpose(status: ExitStatus) -> bool {
+  #[cfg(target_os = "linux")]
+  if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+    return [libc::SIGTERM, libc::SIGINT, libc::SIGHUP].contains(&signal);
+  }
+  status.success()
+}
+
+/// Appends to supervisor.log in the same format as roxy.log, and echoes to
+/// stderr for whatever is running the supervisor
+fn log(install_info: &InstallInfo, level: &str, message: &str) {
+  let line = format!(
+    "[{}] [{}] {}",
+    Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+    level,
+    message
+  );
+  eprintln!("{}", line);
+
+  let file = OpenOptions::new()
+    .create(true)
+    .append(true)
+    .open(install_info.supervisor_log_path());
+  if let Ok(mut file) = file {
+    writeln!(file, "{}", line).ok();
+  }
+}
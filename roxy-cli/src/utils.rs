@@ -1,63 +1,291 @@
-use colored::Colorize;
+use indicatif::ProgressBar;
 use path_clean::PathClean;
 use rand::Rng;
-use std::io::Write;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
 use std::path::PathBuf;
-use std::process::{Command, Stdio};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use std::{env, fs};
-use std::{fs::File, path::Path};
 
-pub fn create_url(path: &str) -> String {
-  let install_info = InstallInfo::get();
+use crate::config::RoxyConfig;
+use crate::download::fetch_file;
+use crate::error::RoxyCliError;
+use crate::extract::extract_archive;
+use crate::verify::ArchiveCheck;
+
+/// URL of `path` on the local server, from the port and protocol in roxy.json
+pub fn create_url(path: &str) -> Result<String, RoxyCliError> {
+  let install_info = InstallInfo::get()?;
   if !install_info.installed {
-    return String::new();
+    return Err(RoxyCliError::NotInstalled);
   }
 
-  match std::fs::read_to_string(install_info.path.join("roxy.json")) {
-    Ok(contents) => {
-      let v: serde_json::Value = serde_json::from_str(&contents).unwrap();
-      let port = v["port"].as_str().unwrap();
-      let use_https = v["useHttps"].as_bool().unwrap();
-
-      format!(
-        "{}://localhost:{}{}",
-        if use_https { "https" } else { "http" },
-        port,
-        path
-      )
-    }
-    Err(_) => String::new(),
+  Ok(RoxyConfig::read(&install_info)?.local_url(path))
+}
+
+/// Requests `/alive`, returning how long roxy took to respond.
+pub fn probe_alive(timeout: Duration) -> Result<Duration, String> {
+  let url = create_url("/alive").map_err(|err| err.to_string())?;
+
+  let client = reqwest::blocking::Client::new();
+  let started = Instant::now();
+  let response = client
+    .get(url)
+    .timeout(timeout)
+    .send()
+    .map_err(|err| format!("/alive did not respond: {}", err))?;
+
+  if response.status().is_success() {
+    Ok(started.elapsed())
+  } else {
+    Err(format!("/alive responded with {}", response.status()))
   }
 }
 
 pub fn is_running() -> bool {
+  let url = match create_url("/alive") {
+    Ok(url) => url,
+    Err(_) => return false,
+  };
   let client = reqwest::blocking::Client::new();
-  let response = client
-    .get(create_url("/alive"))
-    .timeout(Duration::from_secs(1))
-    .send();
+  let response = client.get(url).timeout(Duration::from_secs(1)).send();
 
   response.is_ok()
 }
 
-pub fn download_file(url: &str, path: &PathBuf) {
-  let response =
-    reqwest::blocking::get(url).unwrap_or_else(|_| panic!("Failed to download {}", url));
-  let mut file = File::create(path).unwrap();
-  let content = response
-    .bytes()
-    .unwrap_or_else(|_| panic!("Failed to get the bytes from {}", url));
+fn archive_extension(source: &str) -> &'static str {
+  if source.ends_with(".zip") {
+    "zip"
+  } else {
+    "tar.gz"
+  }
+}
+
+#[cfg(target_os = "windows")]
+const ARCHIVE_EXTENSION: &str = "zip";
+#[cfg(target_os = "linux")]
+const ARCHIVE_EXTENSION: &str = "tar.gz";
+
+/// URL of the roxy source archive for a branch, commit or `refs/tags/<tag>`.
+pub fn roxy_archive_url(git_ref: &str) -> String {
+  format!(
+    "https://github.com/keifufu/roxy/archive/{}.{}",
+    git_ref, ARCHIVE_EXTENSION
+  )
+}
+
+pub fn node_archive_url(version: &str) -> String {
+  let version = version.trim_start_matches('v');
+
+  #[cfg(target_os = "windows")]
+  let platform = "win-x64";
+  #[cfg(target_os = "linux")]
+  let platform = "linux-x64";
+
+  format!(
+    "https://nodejs.org/dist/v{}/node-v{}-{}.{}",
+    version, version, platform, ARCHIVE_EXTENSION
+  )
+}
+
+pub fn node_checksums_url(version: &str) -> String {
+  format!(
+    "https://nodejs.org/dist/v{}/SHASUMS256.txt",
+    version.trim_start_matches('v')
+  )
+}
+
+pub fn release_checksums_url(tag: &str) -> String {
+  format!(
+    "https://github.com/keifufu/roxy/releases/download/{}/SHASUMS256.txt",
+    tag
+  )
+}
+
+/// Last path segment of a URL or path, which is what checksum manifests list.
+pub fn source_file_name(source: &str) -> &str {
+  source.rsplit(['/', '\\']).next().unwrap_or(source)
+}
+
+/// Fetches, verifies and extracts a Node.js archive into `app/node`.
+pub fn fetch_node(
+  source: &str,
+  check: &ArchiveCheck,
+  install_info: &InstallInfo,
+  pb: &ProgressBar,
+) -> Result<(), RoxyCliError> {
+  let node_path = install_info
+    .app_path()
+    .join(format!("node.{}", archive_extension(source)));
+
+  fetch_file(source, &node_path, pb)?;
+  let result = check
+    .verify(&node_path, pb)
+    .and_then(|_| extract_archive(&node_path, &install_info.app_path()));
+  remove_file(&node_path)?;
+  result?;
+
+  // Names are different on linux and windows so we just find it on runtime
+  let app_path = install_info.app_path();
+  let read_error = || RoxyCliError::io(format!("Failed to read {:?}", app_path));
+  for entry in fs::read_dir(&app_path).map_err(read_error())? {
+    let entry = entry.map_err(read_error())?;
+    if entry.path().is_dir() && entry.file_name().to_string_lossy().contains("node") {
+      rename(&entry.path(), &entry.path().with_file_name("node"))?;
+    }
+  }
+
+  Ok(())
+}
+
+/// Fetches and verifies a roxy source archive, then moves its `roxy` folder to `dest`.
+pub fn fetch_roxy(
+  source: &str,
+  check: &ArchiveCheck,
+  install_info: &InstallInfo,
+  dest: &Path,
+  pb: &ProgressBar,
+) -> Result<(), RoxyCliError> {
+  let extract_path = install_info.app_path().join("roxy-download");
+  remove_dir(&extract_path)?;
+  fs::create_dir_all(&extract_path).map_err(RoxyCliError::io(format!(
+    "Failed to create {:?}",
+    extract_path
+  )))?;
+
+  let archive_path = extract_path.join(format!("roxy.{}", archive_extension(source)));
+  let result = fetch_file(source, &archive_path, pb)
+    .and_then(|_| check.verify(&archive_path, pb))
+    .and_then(|_| extract_archive(&archive_path, &extract_path))
+    .and_then(|_| move_roxy_folder(&extract_path, dest));
+  remove_dir(&extract_path)?;
+  result
+}
+
+/// The archive contains a single folder named after the branch or tag,
+/// e.g. roxy-main/roxy or roxy-1.0.1/roxy
+fn move_roxy_folder(extract_path: &Path, dest: &Path) -> Result<(), RoxyCliError> {
+  let source_dir = fs::read_dir(extract_path)
+    .map_err(RoxyCliError::io(format!(
+      "Failed to read {:?}",
+      extract_path
+    )))?
+    .filter_map(|entry| entry.ok())
+    .find(|entry| entry.path().is_dir())
+    .ok_or_else(|| RoxyCliError::Archive(String::from("Downloaded archive is empty")))?
+    .path()
+    .join("roxy");
+
+  rename(&source_dir, dest)
+}
+
+/// Deletes the file at `path`, if there is one
+pub fn remove_file(path: &Path) -> Result<(), RoxyCliError> {
+  match fs::remove_file(path) {
+    Err(err) if err.kind() != io::ErrorKind::NotFound => Err(RoxyCliError::io(format!(
+      "Failed to delete {:?}",
+      path
+    ))(err)),
+    _ => Ok(()),
+  }
+}
+
+/// Deletes the folder at `path` with everything in it, if there is one
+pub fn remove_dir(path: &Path) -> Result<(), RoxyCliError> {
+  match fs::remove_dir_all(path) {
+    Err(err) if err.kind() != io::ErrorKind::NotFound => Err(RoxyCliError::io(format!(
+      "Failed to delete {:?}",
+      path
+    ))(err)),
+    _ => Ok(()),
+  }
+}
 
-  std::io::copy(&mut content.as_ref(), &mut file).unwrap();
+pub fn rename(from: &Path, to: &Path) -> Result<(), RoxyCliError> {
+  fs::rename(from, to).map_err(RoxyCliError::io(format!(
+    "Failed to move {:?} to {:?}",
+    from, to
+  )))
 }
 
-pub fn unzip_file(file: &Path, dest: &Path) {
-  Command::new("tar")
-    .args(["-xf", file.to_str().unwrap(), "-C", dest.to_str().unwrap()])
-    .stdout(Stdio::null())
-    .output()
-    .expect("Failed to extract roxy with tar");
+/// Writes a new file only we can read, replacing one left behind by an
+/// earlier run. `create_new` refuses to follow a symlink put in its place.
+/// Files like this go next to the data they belong to rather than in the
+/// shared temp folder, where anyone could plant a symlink under a predictable
+/// name and have us write through it.
+pub fn write_private(path: &Path, contents: &str) -> io::Result<()> {
+  match fs::remove_file(path) {
+    Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+    _ => {}
+  }
+  let mut options = OpenOptions::new();
+  options.write(true).create_new(true);
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::OpenOptionsExt;
+    options.mode(0o600);
+  }
+  options.open(path)?.write_all(contents.as_bytes())
+}
+
+pub fn write_dot_env(
+  roxy_path: &Path,
+  data_path: &Path,
+  termination_token: &str,
+) -> Result<(), RoxyCliError> {
+  let dot_env_contents = format!(
+    "DATA_PATH={}\nTERMINATION_TOKEN={}",
+    data_path.to_string_lossy(),
+    termination_token
+  );
+  let dot_env_path = roxy_path.join(".env");
+  fs::write(&dot_env_path, dot_env_contents).map_err(RoxyCliError::io(format!(
+    "Failed to write {:?}",
+    dot_env_path
+  )))
+}
+
+/// Returns the tag name of the latest roxy release on GitHub.
+pub fn latest_release_tag() -> Option<String> {
+  let client = reqwest::blocking::Client::new();
+  let response = client
+    .get("https://api.github.com/repos/keifufu/roxy/releases/latest")
+    .header("User-Agent", "roxy-cli")
+    .timeout(Duration::from_secs(10))
+    .send()
+    .ok()?;
+
+  if !response.status().is_success() {
+    return None;
+  }
+
+  let json: serde_json::Value = serde_json::from_str(&response.text().ok()?).ok()?;
+  json["tag_name"].as_str().map(String::from)
+}
+
+/// The last `count` lines of `path`, for showing what went wrong.
+pub fn log_tail(path: &Path, count: usize) -> String {
+  let contents = fs::read_to_string(path).unwrap_or_default();
+  let lines: Vec<&str> = contents.lines().collect();
+  let tail = lines[lines.len().saturating_sub(count)..].join("\n");
+
+  format!("Last {} lines of {:?}:\n{}", count, path, tail)
+}
+
+/// Sleeps for `duration`, waking up early if `stop` returns true.
+/// Returns false if it woke up early.
+pub fn sleep_unless(duration: Duration, stop: impl Fn() -> bool) -> bool {
+  let started = Instant::now();
+  while started.elapsed() < duration {
+    if stop() {
+      return false;
+    }
+    std::thread::sleep(Duration::from_millis(100));
+  }
+  !stop()
 }
 
 pub fn generate_token(length: usize) -> String {
@@ -74,250 +302,248 @@ pub fn generate_token(length: usize) -> String {
   token
 }
 
+#[derive(Clone)]
 pub struct InstallInfo {
   pub installed: bool,
   pub version: String,
   pub path: PathBuf,
   pub termination_token: String,
+  /// Extra environment variables for the server, e.g. NODE_ENV or NODE_OPTIONS.
+  /// These take precedence over roxy's .env
+  pub env: BTreeMap<String, String>,
+  pub log_rotation: LogRotation,
 }
 
-impl InstallInfo {
+/// When the server's log files are rotated, set with `log_rotation` in roxy-cli.json.
+///
+/// Nothing runs in the background next to a detached roxy, so its logs are only
+/// checked when roxy-cli runs: on `start`, `status` and `logs`, and continuously
+/// under `run`, `supervise` and `watchdog`. Run one of those, or `roxy-cli status`
+/// from cron, to keep a long-running detached roxy's logs in check.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogRotation {
+  /// Rotate once a log is larger than this
+  pub max_size_mb: u64,
+  /// Rotate once a log was last rotated this many days ago
+  pub max_age_days: u64,
+  /// Compressed archives kept per log
+  pub keep: usize,
+}
+
+impl Default for LogRotation {
   fn default() -> Self {
     Self {
-      installed: false,
-      version: String::from("---"),
-      path: Self::get_default_path(),
-      termination_token: String::new(),
+      max_size_mb: 10,
+      max_age_days: 7,
+      keep: 5,
     }
   }
+}
 
-  pub fn with_or_default(p: Option<String>) -> Self {
-    Self {
-      installed: false,
-      version: String::from("---"),
-      path: if let Some(p) = p {
+/// roxy-cli.json as it is written to disk
+#[derive(Serialize, Deserialize)]
+struct CliConfig {
+  path: PathBuf,
+  #[serde(default)]
+  version: String,
+  #[serde(default)]
+  termination_token: String,
+  #[serde(default)]
+  env: BTreeMap<String, String>,
+  #[serde(default)]
+  log_rotation: LogRotation,
+}
+
+impl InstallInfo {
+  fn default() -> Result<Self, RoxyCliError> {
+    Self::with_or_default(None)
+  }
+
+  pub fn with_or_default(p: Option<String>) -> Result<Self, RoxyCliError> {
+    let path = match p {
+      Some(p) => {
         let path = PathBuf::from(p);
         if path.is_absolute() {
           path
         } else {
-          env::current_dir().unwrap().join(path)
+          env::current_dir()
+            .map_err(RoxyCliError::io("Failed to get the current directory"))?
+            .join(path)
         }
         .clean()
-      } else {
-        Self::get_default_path()
-      },
+      }
+      None => Self::get_default_path()?,
+    };
+
+    Ok(Self {
+      installed: false,
+      version: String::from("---"),
+      path,
       termination_token: String::new(),
-    }
+      env: BTreeMap::new(),
+      log_rotation: LogRotation::default(),
+    })
   }
 
-  pub fn get() -> Self {
-    let config_path = Self::get_cli_config_path();
-
-    if std::path::Path::new(&config_path).exists() {
-      match std::fs::read_to_string(config_path) {
-        Ok(contents) => {
-          let v: serde_json::Value = serde_json::from_str(&contents).unwrap();
-          let path = v["path"].as_str().unwrap();
-          let valid_path = Path::new(&path).exists();
-
-          Self {
-            installed: valid_path,
-            path: PathBuf::from(path),
-            version: if valid_path {
-              v["version"].as_str().unwrap().to_string()
-            } else {
-              String::from("---")
-            },
-            termination_token: v["termination_token"].as_str().unwrap().to_string(),
-          }
-        }
-        Err(_) => Self::default(),
+  pub fn get() -> Result<Self, RoxyCliError> {
+    let config_path = Self::get_cli_config_path()?;
+
+    let contents = match fs::read_to_string(&config_path) {
+      Ok(contents) => contents,
+      Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Self::default(),
+      Err(err) => {
+        return Err(RoxyCliError::io(format!(
+          "Failed to read {:?}",
+          config_path
+        ))(err))
       }
-    } else {
-      Self::default()
-    }
+    };
+    let config: CliConfig =
+      serde_json::from_str(&contents).map_err(|err| RoxyCliError::ConfigParse {
+        path: config_path,
+        reason: err.to_string(),
+      })?;
+    let installed = config.path.exists();
+
+    Ok(Self {
+      installed,
+      path: config.path,
+      version: if installed {
+        config.version
+      } else {
+        String::from("---")
+      },
+      termination_token: config.termination_token,
+      env: config.env,
+      log_rotation: config.log_rotation,
+    })
   }
 
-  pub fn write(path: PathBuf, version: &str, termination_token: String) {
-    let config_path = Self::get_cli_config_path();
-
-    let json = serde_json::json!({
-      "path": path,
-      "version": version,
-      "termination_token": termination_token
-    });
-
-    if let Some(parent_dir) = Path::new(&config_path).parent() {
-      if let Err(err) = std::fs::create_dir_all(parent_dir) {
-        panic!("Failed to create directories: {}", err);
-      }
+  pub fn write(
+    path: PathBuf,
+    version: &str,
+    termination_token: String,
+  ) -> Result<(), RoxyCliError> {
+    let config_path = Self::get_cli_config_path()?;
+
+    // env and log_rotation are only ever edited by hand, so keep whatever is there
+    let existing = Self::get()?;
+    let config = CliConfig {
+      path,
+      version: version.to_string(),
+      termination_token,
+      env: existing.env,
+      log_rotation: existing.log_rotation,
+    };
+
+    if let Some(parent_dir) = config_path.parent() {
+      fs::create_dir_all(parent_dir).map_err(RoxyCliError::io(format!(
+        "Failed to create {:?}",
+        parent_dir
+      )))?;
     }
+    let json =
+      serde_json::to_string(&config).map_err(|err| RoxyCliError::Other(err.to_string()))?;
+    fs::write(&config_path, json).map_err(RoxyCliError::io(format!(
+      "Failed to write {:?}",
+      config_path
+    )))
+  }
 
-    match File::create(config_path) {
-      Ok(mut file) => {
-        if let Err(err) = file.write_all(json.to_string().as_bytes()) {
-          panic!("Failed to write file: {}", err);
-        }
-      }
-      Err(err) => {
-        panic!("Failed to create file: {}", err);
-      }
+  pub fn reset() -> Result<(), RoxyCliError> {
+    let config_path = Self::get_cli_config_path()?;
+    match fs::remove_file(&config_path) {
+      Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(RoxyCliError::io(format!(
+        "Failed to delete {:?}",
+        config_path
+      ))(err)),
+      _ => Ok(()),
     }
   }
 
-  pub fn reset() {
-    fs::remove_file(Self::get_cli_config_path()).expect("Failed to delete roxy-cli config");
+  pub fn install_log_path(&self) -> PathBuf {
+    self.path.join("install.log")
   }
 
-  pub fn app_path(&self) -> PathBuf {
-    self.path.join("app")
+  /// stdout of the server process when started in the background
+  pub fn server_out_log_path(&self) -> PathBuf {
+    self.path.join("server.out.log")
   }
 
-  pub fn node_path(&self) -> PathBuf {
-    self.app_path().join("node")
+  /// stderr of the server process when started in the background
+  pub fn server_err_log_path(&self) -> PathBuf {
+    self.path.join("server.err.log")
   }
 
-  pub fn roxy_path(&self) -> PathBuf {
-    self.app_path().join("roxy")
+  pub fn pid_path(&self) -> PathBuf {
+    self.path.join("roxy.pid")
   }
 
-  pub fn get_roxy_cli_path() -> PathBuf {
-    let result;
-    #[cfg(target_os = "windows")]
-    {
-      let local_app_data =
-        env::var("LOCALAPPDATA").expect("Failed to retrieve LOCALAPPDATA environment variable");
-      let mut path = PathBuf::from(local_app_data);
-      path.push("roxy-cli");
-      result = path;
-    }
-
-    #[cfg(target_os = "linux")]
-    {
-      let home_dir = env::var("HOME").expect("Failed to retrieve HOME environment variable");
-      let mut path = PathBuf::from(home_dir);
-      path.push(".roxy-cli");
-      result = path;
-    }
+  pub fn supervisor_path(&self) -> PathBuf {
+    self.path.join("supervisor.json")
+  }
 
-    result
+  /// Holds the PID of a server `roxy-cli watchdog` killed for hanging
+  pub fn hang_kill_path(&self) -> PathBuf {
+    self.path.join("hang-kill.pid")
   }
 
-  fn get_cli_config_path() -> PathBuf {
-    Self::get_roxy_cli_path().join("roxy-cli.json")
+  pub fn supervisor_log_path(&self) -> PathBuf {
+    self.path.join("supervisor.log")
   }
 
-  fn get_default_path() -> PathBuf {
-    let result;
+  pub fn diagnostics_path(&self) -> PathBuf {
+    self.path.join("diagnostics")
+  }
 
-    #[cfg(target_os = "windows")]
-    {
-      let local_app_data =
-        env::var("LOCALAPPDATA").expect("Failed to retrieve LOCALAPPDATA environment variable");
-      let mut path = PathBuf::from(local_app_data);
-      path.push("roxy");
-      result = path;
-    }
+  /// Written by the server's own Logger
+  pub fn roxy_log_path(&self) -> PathBuf {
+    self.path.join("roxy.log")
+  }
 
-    #[cfg(target_os = "linux")]
-    {
-      let home_dir = env::var("HOME").expect("Failed to retrieve HOME environment variable");
-      let mut path = PathBuf::from(home_dir);
-      path.push("roxy");
-      result = path;
-    }
+  pub fn app_path(&self) -> PathBuf {
+    self.path.join("app")
+  }
 
-    result
+  pub fn node_path(&self) -> PathBuf {
+    self.app_path().join("node")
   }
-}
 
-pub struct Autostart {}
+  pub fn roxy_path(&self) -> PathBuf {
+    self.app_path().join("roxy")
+  }
 
-// Only supports Windows and Ubuntu
-impl Autostart {
-  pub fn is_enabled() -> bool {
+  pub fn get_roxy_cli_path() -> Result<PathBuf, RoxyCliError> {
     #[cfg(target_os = "windows")]
-    {
-      let output = Command::new("reg")
-        .args([
-          "query",
-          "HKCU\\Software\\Microsoft\\Windows\\CurrentVersion\\Run",
-          "/v",
-          "Roxy",
-        ])
-        .output()
-        .expect("Failed to check Windows autostart status");
-
-      output.status.success()
-    }
+    let path = base_dir()?.join("roxy-cli");
     #[cfg(target_os = "linux")]
-    std::path::Path::new("~/.config/autostart/roxy").exists()
-  }
-
-  pub fn enable() {
-    let install_info = InstallInfo::get();
-    if !install_info.installed {
-      println!("{}", "Roxy is not installed".red().bold());
-      return;
-    }
+    let path = base_dir()?.join(".roxy-cli");
 
-    #[cfg(target_os = "windows")]
-    {
-      let self_path = env::current_exe().expect("Failed to get current executable path");
-      let dest = InstallInfo::get_roxy_cli_path().join("roxy-cli.exe");
-      fs::copy(self_path, &dest).expect("Failed to copy executable");
-
-      // {:?} nicely provides us with quotation marks lol
-      let command = format!("{:?} start", dest);
-      Command::new("reg")
-        .args([
-          "add",
-          "HKCU\\Software\\Microsoft\\Windows\\CurrentVersion\\Run",
-          "/v",
-          "Roxy",
-          "/d",
-          command.as_str(),
-          "/f",
-        ])
-        .output()
-        .expect("Failed to enable autostart");
-    }
+    Ok(path)
+  }
 
-    #[cfg(target_os = "linux")]
-    {
-      let self_path = env::current_exe().expect("Failed to get current executable path");
-      let dest = InstallInfo::get_roxy_cli_path().join("roxy-cli");
-      fs::copy(self_path, &dest).expect("Failed to copy executable");
-
-      // {:?} nicely provides us with quotation marks lol
-      let command = format!("{:?} start", dest);
-      Command::new("sh")
-        .args(&[
-          "-c",
-          format!("echo '{}' >> ~/.config/autostart/roxy", command.as_str()).as_str(),
-        ])
-        .output()
-        .expect("Failed to enable autostart");
-    }
+  fn get_cli_config_path() -> Result<PathBuf, RoxyCliError> {
+    Ok(Self::get_roxy_cli_path()?.join("roxy-cli.json"))
   }
 
-  pub fn disable() {
-    #[cfg(windows)]
-    Command::new("reg")
-      .args([
-        "delete",
-        "HKCU\\Software\\Microsoft\\Windows\\CurrentVersion\\Run",
-        "/v",
-        "Roxy",
-        "/f",
-      ])
-      .output()
-      .expect("Failed to disable autostart");
-
-    #[cfg(unix)]
-    Command::new("rm")
-      .args(&["~/.config/autostart/roxy"])
-      .output()
-      .expect("Failed to disable autostart");
+  fn get_default_path() -> Result<PathBuf, RoxyCliError> {
+    Ok(base_dir()?.join("roxy"))
   }
 }
+
+/// %LocalAppData% on Windows, the home directory on Linux
+fn base_dir() -> Result<PathBuf, RoxyCliError> {
+  #[cfg(target_os = "windows")]
+  let var = "LOCALAPPDATA";
+  #[cfg(target_os = "linux")]
+  let var = "HOME";
+
+  env::var_os(var)
+    .filter(|dir| !dir.is_empty())
+    .map(PathBuf::from)
+    .ok_or_else(|| RoxyCliError::Io {
+      context: String::from("Failed to find roxy-cli's config directory"),
+      source: std::io::Error::new(std::io::ErrorKind::NotFound, format!("{} is not set", var)),
+    })
+}
@@ -0,0 +1,144 @@
+use indicatif::ProgressBar;
+use minisign_verify::{PublicKey, Signature};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use crate::download::fetch_file;
+use crate::error::RoxyCliError;
+use crate::utils::InstallInfo;
+
+/// What a downloaded archive has to match before it gets extracted.
+#[derive(Default)]
+pub struct ArchiveCheck {
+  /// Expected SHA-256 of the archive, as hex
+  pub sha256: Option<String>,
+  /// Signature source (path or URL) and the minisign public key to verify it with
+  pub signature: Option<(String, String)>,
+}
+
+impl ArchiveCheck {
+  pub fn is_empty(&self) -> bool {
+    self.sha256.is_none() && self.signature.is_none()
+  }
+
+  pub fn verify(&self, archive: &Path, pb: &ProgressBar) -> Result<(), RoxyCliError> {
+    if let Some(expected) = &self.sha256 {
+      let actual = sha256_file(archive)?;
+      if !actual.eq_ignore_ascii_case(expected.trim()) {
+        return Err(RoxyCliError::Verification(format!(
+          "Checksum mismatch for {}\n  expected: {}\n  actual:   {}",
+          file_name(archive),
+          expected.trim(),
+          actual
+        )));
+      }
+    }
+
+    if let Some((signature_source, public_key)) = &self.signature {
+      let signature_path = PathBuf::from(format!("{}.minisig", archive.to_string_lossy()));
+      let result = fetch_file(signature_source, &signature_path, pb)
+        .and_then(|_| verify_minisign(archive, &signature_path, public_key));
+      fs::remove_file(&signature_path).ok();
+      result?;
+    }
+
+    Ok(())
+  }
+}
+
+pub fn sha256_file(path: &Path) -> Result<String, RoxyCliError> {
+  let mut file = File::open(path).map_err(RoxyCliError::io(format!(
+    "Failed to open {}",
+    file_name(path)
+  )))?;
+  let mut hasher = Sha256::new();
+  std::io::copy(&mut file, &mut hasher).map_err(RoxyCliError::io(format!(
+    "Failed to read {}",
+    file_name(path)
+  )))?;
+
+  Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Looks up `file_name` in a SHASUMS256.txt style manifest
+/// (`<hex>  <file name>` per line, as published by Node.js).
+pub fn find_checksum(manifest: &str, file_name: &str) -> Option<String> {
+  manifest.lines().find_map(|line| {
+    let mut parts = line.split_whitespace();
+    let checksum = parts.next()?;
+    let name = parts.next()?.trim_start_matches('*');
+    (name == file_name).then(|| checksum.to_string())
+  })
+}
+
+/// Fetches a checksum manifest and returns the entry for `file_name`.
+/// A manifest that can not be fetched means the archive can not be verified,
+/// so that is a verification error too.
+pub fn checksum_from_manifest(
+  source: &str,
+  file_name: &str,
+  pb: &ProgressBar,
+) -> Result<String, RoxyCliError> {
+  // In our own downloads folder, see `write_private`
+  let downloads_path = InstallInfo::get_roxy_cli_path()?.join("downloads");
+  fs::create_dir_all(&downloads_path)
+    .map_err(RoxyCliError::io("Failed to create downloads folder"))?;
+  let manifest_path = downloads_path.join(format!("{}.sha256", std::process::id()));
+  let manifest = fetch_file(source, &manifest_path, pb).and_then(|_| {
+    fs::read_to_string(&manifest_path).map_err(RoxyCliError::io("Failed to read the manifest"))
+  });
+  fs::remove_file(&manifest_path).ok();
+
+  let manifest = match manifest {
+    Err(RoxyCliError::Cancelled) => return Err(RoxyCliError::Cancelled),
+    Err(err) => {
+      return Err(RoxyCliError::Verification(format!(
+        "Failed to read {}: {}",
+        source, err
+      )))
+    }
+    Ok(manifest) => manifest,
+  };
+  find_checksum(&manifest, file_name).ok_or_else(|| {
+    RoxyCliError::Verification(format!("{} has no checksum for {}", source, file_name))
+  })
+}
+
+fn verify_minisign(
+  archive: &Path,
+  signature_path: &Path,
+  public_key: &str,
+) -> Result<(), RoxyCliError> {
+  let public_key = PublicKey::from_base64(public_key.trim()).map_err(|err| {
+    RoxyCliError::InvalidArgument(format!("Invalid minisign public key: {}", err))
+  })?;
+  let signature = Signature::from_file(signature_path).map_err(|err| {
+    RoxyCliError::Verification(format!(
+      "Invalid signature for {}: {}",
+      file_name(archive),
+      err
+    ))
+  })?;
+  let contents = fs::read(archive).map_err(RoxyCliError::io(format!(
+    "Failed to read {}",
+    file_name(archive)
+  )))?;
+
+  public_key
+    .verify(&contents, &signature, false)
+    .map_err(|err| {
+      RoxyCliError::Verification(format!(
+        "Signature verification failed for {}: {}",
+        file_name(archive),
+        err
+      ))
+    })
+}
+
+fn file_name(path: &Path) -> String {
+  path
+    .file_name()
+    .map(|name| name.to_string_lossy().to_string())
+    .unwrap_or_default()
+}
@@ -0,0 +1,111 @@
+use chrono::{Local, SecondsFormat, Utc};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::args::WatchdogOptions;
+use crate::utils::{log_tail, probe_alive, InstallInfo};
+
+/// Lines of the server output and roxy.log kept in a diagnostics file
+const DIAGNOSTICS_LOG_TAIL_LINES: usize = 100;
+
+/// Probes `/alive` and decides when roxy is hung.
+///
+/// A probe fails if it errors, gets a non-2xx response or takes longer than
+/// `max_latency`. After `failures` failed probes in a row roxy counts as hung.
+pub struct Watchdog {
+  options: WatchdogOptions,
+  /// The process being watched and when the watchdog first saw it
+  watching: Option<(u32, Instant)>,
+  failed_probes: Vec<String>,
+}
+
+impl Watchdog {
+  pub fn new(options: WatchdogOptions) -> Self {
+    Self {
+      options,
+      watching: None,
+      failed_probes: Vec::new(),
+    }
+  }
+
+  pub fn interval(&self) -> Duration {
+    Duration::from_secs(self.options.interval)
+  }
+
+  /// Probes roxy running as `pid`. A new process gets `startup_grace` before
+  /// it is probed. Returns why roxy is considered hung, if it is.
+  pub fn probe(&mut self, pid: u32) -> Option<String> {
+    match self.watching {
+      Some((watched, _)) if watched == pid => {}
+      _ => {
+        self.watching = Some((pid, Instant::now()));
+        self.failed_probes.clear();
+      }
+    }
+    if let Some((_, since)) = self.watching {
+      if since.elapsed() < Duration::from_secs(self.options.startup_grace) {
+        return None;
+      }
+    }
+
+    let max_latency = Duration::from_millis(self.options.max_latency);
+    let failure = match probe_alive(Duration::from_secs(self.options.probe_timeout)) {
+      Ok(latency) if latency > max_latency => {
+        format!("/alive took {}ms", latency.as_millis())
+      }
+      Ok(_) => {
+        self.failed_probes.clear();
+        return None;
+      }
+      Err(err) => err,
+    };
+
+    self.failed_probes.push(format!(
+      "[{}] {}",
+      Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+      failure
+    ));
+    if self.failed_probes.len() < self.options.failures as usize {
+      return None;
+    }
+
+    Some(format!(
+      "{} failed probes in a row, last: {}",
+      self.failed_probes.len(),
+      failure
+    ))
+  }
+
+  /// Writes the failed probes and the latest server output to the
+  /// diagnostics folder, returning the file written.
+  pub fn capture_diagnostics(
+    &self,
+    install_info: &InstallInfo,
+    reason: &str,
+  ) -> Result<PathBuf, String> {
+    let folder = install_info.diagnostics_path();
+    fs::create_dir_all(&folder).map_err(|err| format!("Failed to create {:?}: {}", folder, err))?;
+
+    let path = folder.join(format!("hang-{}.log", Local::now().format("%Y%m%d-%H%M%S")));
+    let mut report = format!("roxy was considered hung: {}\n\nFailed probes:\n", reason);
+    for probe in &self.failed_probes {
+      report.push_str(&format!("  {}\n", probe));
+    }
+    for log_path in [
+      install_info.server_out_log_path(),
+      install_info.server_err_log_path(),
+      install_info.roxy_log_path(),
+    ] {
+      if log_path.exists() {
+        report.push_str(&format!(
+          "\n{}\n",
+          log_tail(&log_path, DIAGNOSTICS_LOG_TAIL_LINES)
+        ));
+      }
+    }
+
+    fs::write(&path, report).map_err(|err| format!("Failed to write {:?}: {}", path, err))?;
+    Ok(path)
+  }
+}
@@ -27,6 +27,17 @@ type Secrets = {
 };
 type Env = { dataPath: string; terminationToken: string };
 
+// roxy.json may contain comments and trailing commas, like the old config.jsonc
+const stripJsonc = (contents: string) =>
+  contents
+    .replace(
+      /("(?:\\.|[^"\\])*")|\/\/[^\n]*|\/\*[\s\S]*?\*\//g,
+      (match, string) => string ?? ""
+    )
+    .replace(/("(?:\\.|[^"\\])*")|,(\s*[}\]])/g, (match, string, close) =>
+      string ?? close
+    );
+
 export class Config {
   private static isInitialized = false;
   private static config: ConfigType;
@@ -81,7 +92,7 @@ export class Config {
         "utf-8"
       );
 
-    return JSON.parse(fs.readFileSync(configPath, "utf-8"));
+    return JSON.parse(stripJsonc(fs.readFileSync(configPath, "utf-8")));
   };
 
   private static readSecrets = () => {